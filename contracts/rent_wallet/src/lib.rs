@@ -1,1081 +1,7733 @@
 #![no_std]
 
-
-
-use soroban_sdk::{contract, contractimpl, contracttype, Address, Env, Map, Symbol};
-
-
+use soroban_sdk::{
+    contract, contracterror, contractimpl, contracttype, symbol_short, token, Address, BytesN, Env, Map,
+    Symbol, TryFromVal, Val, Vec,
+};
 
 #[contracttype]
-
 #[derive(Clone)]
-
 pub enum DataKey {
-
     Admin,
-
+    /// Legacy instance-storage balance map, drained by `migrate_balances`.
     Balances,
-
+    /// Legacy all-or-nothing pause flag, superseded by `PauseFlags`.
     Paused,
+    Token,
+    Operators,
+    Balance(Address),
+    DefaultDebitCap,
+    DebitCap(Address),
+    CapUsage(Address),
+    PendingAdmin,
+    Hold(BytesN<32>),
+    UserHolds(Address),
+    JournalCount(Address),
+    JournalEntry(Address, u64),
+    ProcessedRef(BytesN<32>),
+    Assets,
+    AssetBalance(Address, Address),
+    Frozen(Address),
+    PauseFlags,
+    Version,
+    TtlConfig,
+    InstanceLiveUntil,
+    BalanceLiveUntil(Address),
+    Allowance(Address, Address),
+    Totals,
+    StandingOrderCount,
+    StandingOrder(u64),
+    CreditLimit(Address),
+    FeeConfig,
+    AccountCount,
+    AccountAt(u64),
+    AccountIndex(Address),
+    LockedGrants(Address),
+    Buckets(Address),
+    QueueConfig,
+    WithdrawalCount,
+    Withdrawal(u64),
+    QueuedAmount(Address),
+    QueuedAssetAmount(Address, Address),
+    BreakerConfig,
+    OutflowSlots,
+    Owed,
+    AssetDebitCap(Address, Address),
+    AssetCapUsage(Address, Address),
 }
 
+/// Maximum amount a user may be debited within one cap period.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DebitCap {
+    pub limit: i128,
+    /// Length of a cap period in seconds. Periods are aligned to multiples of
+    /// this length since the Unix epoch, e.g. 86400 gives UTC calendar days.
+    pub period_secs: u64,
+}
 
+/// Amount debited from a user within the period starting at `period_start`.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CapUsage {
+    pub period_start: u64,
+    pub spent: i128,
+}
 
-#[contract]
-
-pub struct RentWallet;
-
-
+/// Paginated result for list_accounts
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccountPage {
+    /// `(account, balance)` pairs in the order accounts were first credited or debited.
+    /// `balance` is the default-asset balance, so accounts holding only other
+    /// assets are listed with `0`.
+    pub accounts: Vec<(Address, i128)>,
+    pub has_next: bool,
+    /// Position of the last account returned; only valid if `has_next` is true.
+    pub next_cursor: u64,
+}
 
-fn balances(env: &Env) -> Map<Address, i128> {
+/// Storage TTL policy, in ledgers. An entry is extended to `extend_to` once
+/// its remaining TTL drops to `threshold` or below.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TtlConfig {
+    pub threshold: u32,
+    pub extend_to: u32,
+}
 
-    env.storage()
+/// Admin handover started by `propose_admin` and waiting for `accept_admin`.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PendingAdmin {
+    pub new_admin: Address,
+    /// Ledger timestamp after which the proposal can no longer be accepted.
+    pub expires_at: Option<u64>,
+}
 
-        .instance()
+/// Funds reserved from a user's balance until captured, released or expired.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hold {
+    pub user: Address,
+    pub amount: i128,
+    /// Ledger timestamp after which the hold stops reserving funds.
+    pub expires_at: u64,
+}
 
-        .get::<_, Map<Address, i128>>(&DataKey::Balances)
+/// Amount `spender` may still debit from `owner` with `debit_from`.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Allowance {
+    pub amount: i128,
+    /// Last ledger sequence on which the allowance can be used.
+    pub expiration_ledger: u32,
+}
 
-        .unwrap_or_else(|| Map::new(env))
+/// Recurring payment from `user` to `beneficiary`, run by `execute_due`.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StandingOrder {
+    pub user: Address,
+    pub beneficiary: Address,
+    pub amount: i128,
+    pub interval_secs: u64,
+    /// Ledger timestamp from which the next run may execute.
+    pub next_due: u64,
+    /// Runs left, paid or missed.
+    pub remaining: u32,
+    pub executed: u32,
+    /// Runs skipped because the user's available balance was too low.
+    pub missed: u32,
+}
 
+/// Platform fee charged on `debit`: `bps` basis points of the amount, but at
+/// least `min_fee`, credited to the wallet balance of `treasury`.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FeeConfig {
+    pub bps: u32,
+    pub min_fee: i128,
+    pub treasury: Address,
 }
 
+/// Pauses debits and withdrawals once more than `threshold` has left the
+/// wallet within the last `window_secs`.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BreakerConfig {
+    pub threshold: i128,
+    pub window_secs: u64,
+}
 
+/// Outflows above `threshold` wait `delay_secs` in the withdrawal queue,
+/// during which `guardian` can cancel them.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueueConfig {
+    pub threshold: i128,
+    pub delay_secs: u64,
+    pub guardian: Address,
+}
 
-fn put_balances(env: &Env, b: Map<Address, i128>) {
+/// Call that produced a queued withdrawal, with what it needs to run later.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outflow {
+    /// `debit` or `debit_with_ref`, with the reference if any.
+    Debit(Option<BytesN<32>>),
+    /// `debit_bucket` from the named bucket.
+    Bucket(Symbol),
+    /// `capture` of the given hold.
+    Capture(BytesN<32>),
+    /// `debit_asset` of a non-default asset.
+    Asset(Address),
+    /// `withdraw` of tokens to the given recipient.
+    Withdraw(Address),
+}
 
-    env.storage().instance().set(&DataKey::Balances, &b)
+/// What an outflow call did with the funds.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OutflowStatus {
+    /// The funds moved.
+    Executed,
+    /// The outflow waits in the withdrawal queue under this id.
+    Queued(u64),
+    /// The outflow would have tripped the circuit breaker. No funds moved
+    /// and the contract is now paused for debits and withdrawals.
+    Blocked,
+}
 
+/// Large outflow waiting in the withdrawal queue.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueuedWithdrawal {
+    pub user: Address,
+    pub amount: i128,
+    pub outflow: Outflow,
+    /// Guardian at queue time. Only it can cancel, even if the queue config
+    /// changes later.
+    pub guardian: Address,
+    /// Ledger timestamp from which `execute_withdrawal` may run.
+    pub execute_after: u64,
 }
 
+/// Wallet-wide running totals of the default-asset balances.
+#[contracttype]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Totals {
+    /// Sum of all user balances.
+    pub balance: i128,
+    /// Everything ever credited into the wallet. Transfers are not counted.
+    pub credited: i128,
+    /// Everything ever debited out of the wallet. Transfers are not counted.
+    pub debited: i128,
+}
 
+/// Result of `check_invariants`.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvariantReport {
+    pub totals: Totals,
+    /// `totals.balance == totals.credited - totals.debited`.
+    pub totals_consistent: bool,
+    /// Sum of all positive balances, i.e. what the wallet owes its users.
+    /// Drawn credit does not offset it.
+    pub owed: i128,
+    /// Balance of the configured token held by the contract, if a token is set.
+    pub token_holdings: Option<i128>,
+    /// Whether `token_holdings` covers `owed`, if a token is set.
+    pub fully_backed: Option<bool>,
+}
 
-fn require_admin(env: &Env) {
+/// Funds credited to a user that vest linearly from `start` to `end`. A plain
+/// time lock has `start == end`. Vested funds join the balance through
+/// `claim_unlocked`.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LockedGrant {
+    pub amount: i128,
+    /// Part of `amount` already moved into the balance.
+    pub claimed: i128,
+    pub start: u64,
+    pub end: u64,
+}
 
-    let admin: Address = env
+/// Result of `balance_details`.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BalanceDetails {
+    /// The regular balance that debits draw on.
+    pub spendable: i128,
+    /// Granted funds that have not vested yet.
+    pub locked: i128,
+    /// Vested funds waiting for `claim_unlocked`.
+    pub claimable: i128,
+}
 
-        .storage()
+/// One balance movement in a user's journal.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JournalEntry {
+    /// Position in the user's journal, starting at 0.
+    pub seq: u64,
+    /// Operation that produced the entry, e.g. `credit`, `debit`, `transfer_in`.
+    pub kind: Symbol,
+    /// Amount moved (always positive).
+    pub amount: i128,
+    /// Balance after the movement.
+    pub balance: i128,
+    /// Ledger timestamp of the movement.
+    pub timestamp: u64,
+    /// External reference, such as a hold id.
+    pub reference: Option<BytesN<32>>,
+    /// Asset moved, or `None` for the default asset.
+    pub asset: Option<Address>,
+}
 
-        .instance()
+/// Paginated result for list_entries
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JournalPage {
+    pub entries: Vec<JournalEntry>,
+    pub has_next: bool, // True if there are more entries
+    pub next_cursor: u64, // Cursor for next page (only valid if has_next is true)
+}
 
-        .get(&DataKey::Admin)
+/// Contract error types
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum ContractError {
+    /// Debit would exceed the user's cap for the current period
+    DebitCapExceeded = 1,
+    /// Contract has already been initialized
+    AlreadyInitialized = 2,
+    /// Contract has not been initialized
+    NotInitialized = 3,
+    /// Contract is currently paused
+    ContractPaused = 4,
+    /// Amount is invalid (zero or negative)
+    InvalidAmount = 5,
+    /// Balance is lower than the requested debit
+    InsufficientBalance = 6,
+    /// Arithmetic on a balance or counter would overflow
+    Overflow = 7,
+    /// Caller is not a registered operator
+    NotOperator = 8,
+    /// No backing token has been configured
+    TokenNotSet = 9,
+    /// Token does not match the configured backing token
+    UnsupportedToken = 10,
+    /// There is no legacy balance map left to migrate
+    NoLegacyBalances = 11,
+    /// Debit cap has a negative limit or a zero-length period
+    InvalidDebitCap = 12,
+    /// Expiry timestamp is not in the future
+    InvalidExpiry = 13,
+    /// There is no pending admin handover
+    NoPendingAdmin = 14,
+    /// Pending admin handover has expired
+    AdminTransferExpired = 15,
+    /// A hold with this id already exists
+    DuplicateHold = 16,
+    /// No hold exists with this id
+    HoldNotFound = 17,
+    /// Hold has expired and can no longer be captured
+    HoldExpired = 18,
+    /// Capture amount is larger than the held amount
+    CaptureExceedsHold = 19,
+    /// Sender and recipient of a transfer are the same account
+    SelfTransfer = 20,
+    /// Page limit is outside 1..=100
+    InvalidLimit = 21,
+    /// Reference has already been processed (duplicate)
+    DuplicateReference = 22,
+    /// Account is frozen
+    AccountFrozen = 23,
+    /// Account is not frozen
+    AccountNotFrozen = 24,
+    /// Pause flags contain unknown bits
+    InvalidPauseFlags = 25,
+    /// Storage is already at the current schema version
+    AlreadyMigrated = 26,
+    /// TTL threshold exceeds the extension or the extension exceeds the network maximum
+    InvalidTtlConfig = 27,
+    /// Spender's allowance is lower than the requested amount
+    InsufficientAllowance = 28,
+    /// Standing order has a zero interval or run count
+    InvalidSchedule = 29,
+    /// Standing order does not exist or has finished
+    StandingOrderNotFound = 30,
+    /// Standing order's next run is not due yet
+    StandingOrderNotDue = 31,
+    /// Fee basis points exceed 10,000 or the minimum fee is negative
+    InvalidFeeConfig = 32,
+    /// Bucket name is reserved or both sides of a move are the same bucket
+    InvalidBucket = 33,
+    /// Queue threshold is not positive or the delay is zero
+    InvalidQueueConfig = 34,
+    /// Queued withdrawal does not exist, was executed or was cancelled
+    WithdrawalNotFound = 35,
+    /// Queued withdrawal's delay has not passed yet
+    WithdrawalNotReady = 36,
+    /// Breaker threshold is not positive or the window is shorter than
+    /// `BREAKER_SLOTS` seconds
+    InvalidBreakerConfig = 37,
+    /// Backing token cannot change while balances are outstanding
+    TokenInUse = 38,
+    /// Debit of a non-default asset from a capped user without a cap for that asset
+    AssetCapNotSet = 39,
+}
 
-        .expect("admin not set");
+#[contract]
+pub struct RentWallet;
 
-    admin.require_auth();
+/// Pause flag for inflows: `credit`, `credit_as_operator`, `credit_with_ref`,
+/// `credit_asset`, `credit_bucket`, `credit_locked`, `credit_vesting`,
+/// `claim_unlocked` and `deposit`.
+pub const PAUSE_CREDIT: u32 = 1 << 0;
+/// Pause flag for admin outflows: `debit`, `debit_with_ref`, `debit_asset`,
+/// `debit_bucket`, `hold`, `capture` and queued debits in
+/// `execute_withdrawal`. Also blocks `debit_from`.
+pub const PAUSE_DEBIT: u32 = 1 << 1;
+/// Pause flag for token payouts: `withdraw`, also when queued.
+pub const PAUSE_WITHDRAW: u32 = 1 << 2;
+/// Pause flag for movements between wallet balances: `transfer`,
+/// `debit_from` and standing order runs in `execute_due`.
+pub const PAUSE_TRANSFER: u32 = 1 << 3;
+/// Every pause flag.
+pub const PAUSE_ALL: u32 = PAUSE_CREDIT | PAUSE_DEBIT | PAUSE_WITHDRAW | PAUSE_TRANSFER;
+
+/// Storage schema version written by this build. `migrate()` brings older
+/// deployments up to it after an `upgrade()`.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Bucket name that stands for the unallocated part of a balance in
+/// `move_between_buckets`.
+pub const MAIN_BUCKET: Symbol = symbol_short!("main");
+
+/// Number of slots the circuit breaker window is split into. Outflows are
+/// bucketed per slot, so the window rolls forward one slot at a time.
+pub const BREAKER_SLOTS: u64 = 12;
+
+/// Largest page `list_entries` returns.
+pub const MAX_PAGE_LIMIT: u32 = 100;
+
+const DAY_IN_LEDGERS: u32 = 17280;
+
+pub(crate) const BALANCE_BUMP_AMOUNT: u32 = 30 * DAY_IN_LEDGERS;
+
+pub(crate) const BALANCE_LIFETIME_THRESHOLD: u32 = BALANCE_BUMP_AMOUNT - DAY_IN_LEDGERS;
+
+fn read_balance(env: &Env, user: &Address) -> i128 {
+    let key = DataKey::Balance(user.clone());
+    match env.storage().persistent().get::<_, i128>(&key) {
+        Some(balance) => {
+            extend_balance_ttl(env, user);
+            balance
+        }
+        None => 0,
+    }
+}
 
+fn write_balance(env: &Env, user: &Address, balance: i128) -> Result<(), ContractError> {
+    let key = DataKey::Balance(user.clone());
+    let old = env.storage().persistent().get::<_, i128>(&key).unwrap_or(0);
+    let owed = owed(env)
+        .checked_add(balance.max(0))
+        .and_then(|owed| owed.checked_sub(old.max(0)))
+        .ok_or(ContractError::Overflow)?;
+    env.storage().persistent().set(&key, &balance);
+    env.storage().instance().set(&DataKey::Owed, &owed);
+    index_account(env, user);
+    extend_balance_ttl(env, user);
+    Ok(())
 }
 
+/// Sum of all positive default-asset balances.
+fn owed(env: &Env) -> i128 {
+    env.storage().instance().get(&DataKey::Owed).unwrap_or(0)
+}
 
-fn get_paused_state(env: &Env) -> bool {
+fn account_count(env: &Env) -> u64 {
     env.storage()
         .instance()
-        .get::<_, bool>(&DataKey::Paused)
-        .unwrap_or(false)
+        .get(&DataKey::AccountCount)
+        .unwrap_or(0)
 }
 
-fn require_not_paused(env: &Env) {
-    if get_paused_state(env) {
-        panic!("contract is paused")
+/// Appends `user` to the account index the first time its balance of any
+/// asset is written. Accounts are never removed, so positions stay stable. The index
+/// entries share the TTL of the balance entry.
+fn index_account(env: &Env, user: &Address) {
+    let index_key = DataKey::AccountIndex(user.clone());
+    if env.storage().persistent().has(&index_key) {
+        return;
     }
+    let position = account_count(env);
+    let at_key = DataKey::AccountAt(position);
+    env.storage().persistent().set(&index_key, &position);
+    env.storage().persistent().set(&at_key, user);
+    env.storage()
+        .instance()
+        .set(&DataKey::AccountCount, &(position + 1));
 }
 
+/// Extends the index entries of `user`. Accounts with a default-asset
+/// balance have them extended with the balance entry instead; this covers
+/// accounts that only hold other assets.
+fn extend_account_index(env: &Env, user: &Address) {
+    if let Some(position) = read_persistent::<u64>(env, &DataKey::AccountIndex(user.clone())) {
+        extend_persistent(env, &DataKey::AccountAt(position));
+    }
+}
 
-#[contractimpl]
-
-impl RentWallet {
-
-    pub fn init(env: Env, admin: Address) {
+fn ttl_config(env: &Env) -> TtlConfig {
+    env.storage()
+        .instance()
+        .get(&DataKey::TtlConfig)
+        .unwrap_or(TtlConfig {
+            threshold: BALANCE_LIFETIME_THRESHOLD,
+            extend_to: BALANCE_BUMP_AMOUNT,
+        })
+}
 
-        if env.storage().instance().has(&DataKey::Admin) {
+/// Extends a persistent entry by the configured amount once its TTL drops
+/// to the configured threshold.
+fn extend_persistent(env: &Env, key: &DataKey) {
+    let config = ttl_config(env);
+    env.storage()
+        .persistent()
+        .extend_ttl(key, config.threshold, config.extend_to);
+}
 
-            panic!("already initialized")
+/// Reads a persistent entry and extends it like `extend_persistent`, so an
+/// entry that is only ever read stays alive for as long as it is used.
+fn read_persistent<V: TryFromVal<Env, Val>>(env: &Env, key: &DataKey) -> Option<V> {
+    let value = env.storage().persistent().get::<_, V>(key);
+    if value.is_some() {
+        extend_persistent(env, key);
+    }
+    value
+}
 
-        }
+/// Extends every entry of `user` except the balance and journal entries.
+/// Reading through `read_persistent` extends whatever exists.
+fn extend_user_entries(env: &Env, user: &Address) {
+    frozen_reason(env, user);
+    credit_limit(env, user);
+    effective_debit_cap(env, user);
+    read_persistent::<CapUsage>(env, &DataKey::CapUsage(user.clone()));
+    held_amount(env, user);
+    read_locked_grants(env, user);
+    read_buckets(env, user);
+    queued_amount(env, user);
+    extend_account_index(env, user);
+}
 
-        env.storage().instance().set(&DataKey::Admin, &admin);
+/// Live-until ledger last recorded for `live_key`, or 0 if never recorded.
+fn tracked_live_until(env: &Env, live_key: &DataKey) -> u32 {
+    let stored = if matches!(live_key, DataKey::InstanceLiveUntil) {
+        env.storage().instance().get::<_, u32>(live_key)
+    } else {
+        env.storage().persistent().get::<_, u32>(live_key)
+    };
+    stored.unwrap_or(0)
+}
 
+/// Extends the contract instance and records its live-until ledger so
+/// `instance_ttl` can report it. The host does not expose TTLs to contracts,
+/// so the contract keeps its own lower-bound copy.
+fn extend_instance_ttl(env: &Env) {
+    let config = ttl_config(env);
+    let seq = env.ledger().sequence();
+    let live_until = tracked_live_until(env, &DataKey::InstanceLiveUntil);
+    if live_until.saturating_sub(seq) <= config.threshold {
         env.storage()
-
             .instance()
+            .extend_ttl(config.extend_to, config.extend_to);
+        env.storage()
+            .instance()
+            .set(&DataKey::InstanceLiveUntil, &(seq + config.extend_to));
+    }
+}
 
-            .set(&DataKey::Balances, &Map::<Address, i128>::new(&env));
-
+/// Extends the balance entry of `user` and its account index entries,
+/// tracking their live-until ledger the same way as `extend_instance_ttl`.
+fn extend_balance_ttl(env: &Env, user: &Address) {
+    let key = DataKey::Balance(user.clone());
+    if !env.storage().persistent().has(&key) {
+        return;
+    }
+    let config = ttl_config(env);
+    let seq = env.ledger().sequence();
+    let live_key = DataKey::BalanceLiveUntil(user.clone());
+    let live_until = tracked_live_until(env, &live_key);
+    if live_until.saturating_sub(seq) <= config.threshold {
+        let live_until = seq + config.extend_to;
+        env.storage().persistent().set(&live_key, &live_until);
+        let mut keys = Vec::from_array(env, [key, live_key]);
+        let index_key = DataKey::AccountIndex(user.clone());
+        if let Some(position) = env.storage().persistent().get::<_, u64>(&index_key) {
+            keys.push_back(index_key);
+            keys.push_back(DataKey::AccountAt(position));
+        }
+        for key in keys.iter() {
+            env.storage()
+                .persistent()
+                .extend_ttl(&key, config.extend_to, config.extend_to);
+        }
+    }
+}
 
+fn require_admin(env: &Env) -> Result<(), ContractError> {
+    let admin: Address = env
+        .storage()
+        .instance()
+        .get(&DataKey::Admin)
+        .ok_or(ContractError::NotInitialized)?;
+    admin.require_auth();
+    extend_instance_ttl(env);
+    Ok(())
+}
 
-        env.events().publish((Symbol::new(&env, "init"),), admin);
+fn operators(env: &Env) -> Map<Address, bool> {
+    env.storage()
+        .instance()
+        .get::<_, Map<Address, bool>>(&DataKey::Operators)
+        .unwrap_or_else(|| Map::new(env))
+}
 
+fn require_operator(env: &Env, operator: &Address) -> Result<(), ContractError> {
+    operator.require_auth();
+    if !operators(env).get(operator.clone()).unwrap_or(false) {
+        return Err(ContractError::NotOperator);
     }
+    extend_instance_ttl(env);
+    Ok(())
+}
 
+/// Current pause bitmask. Falls back to the legacy `Paused` boolean for
+/// deployments that have not changed their pause state since upgrading.
+fn pause_flags(env: &Env) -> u32 {
+    if let Some(flags) = env.storage().instance().get::<_, u32>(&DataKey::PauseFlags) {
+        return flags;
+    }
+    if env
+        .storage()
+        .instance()
+        .get::<_, bool>(&DataKey::Paused)
+        .unwrap_or(false)
+    {
+        PAUSE_ALL
+    } else {
+        0
+    }
+}
 
+/// Stores a new pause bitmask and emits a `pause_flags` event with the old
+/// and new value.
+fn set_pause_flags(env: &Env, flags: u32) -> Result<(), ContractError> {
+    if flags & !PAUSE_ALL != 0 {
+        return Err(ContractError::InvalidPauseFlags);
+    }
+    let old = pause_flags(env);
+    env.storage().instance().set(&DataKey::PauseFlags, &flags);
+    env.storage().instance().remove(&DataKey::Paused);
+    env.events()
+        .publish((Symbol::new(env, "pause_flags"),), (old, flags));
+    Ok(())
+}
 
-    pub fn credit(env: Env, user: Address, amount: i128) {
+/// Stored schema version. Deployments that predate versioning read as 0.
+fn schema_version(env: &Env) -> u32 {
+    env.storage()
+        .instance()
+        .get::<_, u32>(&DataKey::Version)
+        .unwrap_or(0)
+}
 
-        require_admin(&env);
+/// Moves the legacy instance-storage balance map into per-user entries.
+/// Returns the number of accounts moved.
+fn move_legacy_balances(env: &Env) -> Result<u32, ContractError> {
+    let legacy = env
+        .storage()
+        .instance()
+        .get::<_, Map<Address, i128>>(&DataKey::Balances)
+        .ok_or(ContractError::NoLegacyBalances)?;
+
+    for (user, amount) in legacy.iter() {
+        let balance = read_balance(env, &user)
+            .checked_add(amount)
+            .ok_or(ContractError::Overflow)?;
+        write_balance(env, &user, balance)?;
+        record_entry(env, &user, "migrate", amount, balance, None, None);
+        update_totals(env, amount, 0)?;
+    }
+    env.storage().instance().remove(&DataKey::Balances);
+    Ok(legacy.len())
+}
 
-        require_not_paused(&env);
-        if amount <= 0 {
+fn require_not_paused(env: &Env, operation: u32) -> Result<(), ContractError> {
+    if pause_flags(env) & operation != 0 {
+        return Err(ContractError::ContractPaused);
+    }
+    extend_instance_ttl(env);
+    Ok(())
+}
 
-            panic!("amount must be positive")
+fn frozen_reason(env: &Env, user: &Address) -> Option<u32> {
+    read_persistent(env, &DataKey::Frozen(user.clone()))
+}
 
-        }
+fn require_not_frozen(env: &Env, user: &Address) -> Result<(), ContractError> {
+    if frozen_reason(env, user).is_some() {
+        return Err(ContractError::AccountFrozen);
+    }
+    Ok(())
+}
 
+fn require_positive(amount: i128) -> Result<(), ContractError> {
+    if amount <= 0 {
+        return Err(ContractError::InvalidAmount);
+    }
+    Ok(())
+}
 
+fn get_token(env: &Env) -> Result<Address, ContractError> {
+    env.storage()
+        .instance()
+        .get::<_, Address>(&DataKey::Token)
+        .ok_or(ContractError::TokenNotSet)
+}
 
-        let mut b = balances(&env);
+fn effective_debit_cap(env: &Env, user: &Address) -> Option<DebitCap> {
+    read_persistent::<DebitCap>(env, &DataKey::DebitCap(user.clone()))
+        .or_else(|| env.storage().instance().get(&DataKey::DefaultDebitCap))
+}
 
-        let cur = b.get(user.clone()).unwrap_or(0);
+fn validate_debit_cap(cap: &Option<DebitCap>) -> Result<(), ContractError> {
+    if let Some(cap) = cap {
+        if cap.limit < 0 || cap.period_secs == 0 {
+            return Err(ContractError::InvalidDebitCap);
+        }
+    }
+    Ok(())
+}
 
-        b.set(user.clone(), cur + amount);
+/// Usage stored under `usage_key` for the cap period containing the current
+/// ledger timestamp. Usage recorded for an earlier period reads as zero.
+fn current_cap_usage(env: &Env, usage_key: &DataKey, cap: &DebitCap) -> CapUsage {
+    let now = env.ledger().timestamp();
+    let period_start = now - now % cap.period_secs;
+    match read_persistent::<CapUsage>(env, usage_key) {
+        Some(usage) if usage.period_start == period_start => usage,
+        _ => CapUsage {
+            period_start,
+            spent: 0,
+        },
+    }
+}
 
-        put_balances(&env, b);
+/// Records `amount` against the user's debit cap, failing with
+/// `ContractError::DebitCapExceeded` if it does not fit in the current period.
+fn consume_debit_cap(env: &Env, user: &Address, amount: i128) -> Result<(), ContractError> {
+    let Some(cap) = effective_debit_cap(env, user) else {
+        return Ok(());
+    };
+    consume_cap(env, &cap, &DataKey::CapUsage(user.clone()), amount)
+}
 
+fn asset_debit_cap(env: &Env, user: &Address, asset: &Address) -> Option<DebitCap> {
+    read_persistent(env, &DataKey::AssetDebitCap(user.clone(), asset.clone()))
+}
 
+/// Records a debit of a non-default `asset` against the user's cap for that
+/// asset. A user with a default-asset cap but no cap for `asset` cannot be
+/// debited in it, so a second asset never bypasses the cap.
+fn consume_asset_debit_cap(
+    env: &Env,
+    user: &Address,
+    asset: &Address,
+    amount: i128,
+) -> Result<(), ContractError> {
+    match asset_debit_cap(env, user, asset) {
+        Some(cap) => {
+            let usage_key = DataKey::AssetCapUsage(user.clone(), asset.clone());
+            consume_cap(env, &cap, &usage_key, amount)
+        }
+        None if effective_debit_cap(env, user).is_some() => Err(ContractError::AssetCapNotSet),
+        None => Ok(()),
+    }
+}
 
-        env.events()
+fn consume_cap(
+    env: &Env,
+    cap: &DebitCap,
+    usage_key: &DataKey,
+    amount: i128,
+) -> Result<(), ContractError> {
+    let mut usage = current_cap_usage(env, usage_key, cap);
+    usage.spent = usage
+        .spent
+        .checked_add(amount)
+        .ok_or(ContractError::Overflow)?;
+    if usage.spent > cap.limit {
+        return Err(ContractError::DebitCapExceeded);
+    }
+    env.storage().persistent().set(usage_key, &usage);
+    extend_persistent(env, usage_key);
+    Ok(())
+}
 
-            .publish((Symbol::new(&env, "credit"), user), amount);
+fn read_user_holds(env: &Env, user: &Address) -> Vec<BytesN<32>> {
+    read_persistent(env, &DataKey::UserHolds(user.clone())).unwrap_or_else(|| Vec::new(env))
+}
 
+fn write_user_holds(env: &Env, user: &Address, holds: &Vec<BytesN<32>>) {
+    let key = DataKey::UserHolds(user.clone());
+    if holds.is_empty() {
+        env.storage().persistent().remove(&key);
+    } else {
+        env.storage().persistent().set(&key, holds);
+        extend_persistent(env, &key);
     }
+}
 
+fn read_locked_grants(env: &Env, user: &Address) -> Vec<LockedGrant> {
+    read_persistent(env, &DataKey::LockedGrants(user.clone())).unwrap_or_else(|| Vec::new(env))
+}
 
+fn write_locked_grants(env: &Env, user: &Address, grants: &Vec<LockedGrant>) {
+    let key = DataKey::LockedGrants(user.clone());
+    if grants.is_empty() {
+        env.storage().persistent().remove(&key);
+    } else {
+        env.storage().persistent().set(&key, grants);
+        extend_persistent(env, &key);
+    }
+}
 
-    pub fn debit(env: Env, user: Address, amount: i128) {
+/// Part of `grant` vested at `now`, i.e. `amount * elapsed / duration`
+/// rounded down. Splits `amount` by `duration` first so that no
+/// intermediate product can overflow, however large the grant.
+fn vested_amount(grant: &LockedGrant, now: u64) -> i128 {
+    if now >= grant.end {
+        return grant.amount;
+    }
+    if now <= grant.start {
+        return 0;
+    }
+    let elapsed = now - grant.start;
+    let duration = grant.end - grant.start;
+    let whole = grant.amount / duration as i128;
+    let rest = (grant.amount % duration as i128) as u128;
+    // `whole * elapsed` stays below `amount`, and `rest * elapsed` is below
+    // `duration * elapsed`, which fits in a u128.
+    whole * elapsed as i128 + (rest * elapsed as u128 / duration as u128) as i128
+}
 
-        require_admin(&env);
+/// Adds a grant for `user` after the checks shared by `credit_locked` and
+/// `credit_vesting`.
+fn add_locked_grant(
+    env: &Env,
+    user: &Address,
+    amount: i128,
+    start: u64,
+    end: u64,
+) -> Result<(), ContractError> {
+    require_positive(amount)?;
+    require_not_frozen(env, user)?;
+    if end <= env.ledger().timestamp() {
+        return Err(ContractError::InvalidExpiry);
+    }
+    let mut grants = read_locked_grants(env, user);
+    grants.push_back(LockedGrant {
+        amount,
+        claimed: 0,
+        start,
+        end,
+    });
+    write_locked_grants(env, user, &grants);
+    Ok(())
+}
 
-        require_not_paused(&env);
-        if amount <= 0 {
+fn read_hold(env: &Env, hold_id: &BytesN<32>) -> Result<Hold, ContractError> {
+    read_persistent(env, &DataKey::Hold(hold_id.clone())).ok_or(ContractError::HoldNotFound)
+}
 
-            panic!("amount must be positive")
+/// Deletes a hold and unlinks it from its user.
+fn remove_hold(env: &Env, hold_id: &BytesN<32>, hold: &Hold) {
+    env.storage()
+        .persistent()
+        .remove(&DataKey::Hold(hold_id.clone()));
+    let mut holds = read_user_holds(env, &hold.user);
+    if let Some(index) = holds.first_index_of(hold_id) {
+        holds.remove(index);
+    }
+    write_user_holds(env, &hold.user, &holds);
+}
 
+/// Deletes every expired hold of `user`.
+fn prune_expired_holds(env: &Env, user: &Address) {
+    let now = env.ledger().timestamp();
+    let mut active = Vec::new(env);
+    for hold_id in read_user_holds(env, user).iter() {
+        let key = DataKey::Hold(hold_id.clone());
+        match env.storage().persistent().get::<_, Hold>(&key) {
+            Some(hold) if hold.expires_at >= now => active.push_back(hold_id),
+            _ => env.storage().persistent().remove(&key),
         }
+    }
+    write_user_holds(env, user, &active);
+}
 
+/// Sum of all unexpired holds on `user`.
+fn held_amount(env: &Env, user: &Address) -> i128 {
+    let now = env.ledger().timestamp();
+    let mut total = 0i128;
+    for hold_id in read_user_holds(env, user).iter() {
+        if let Some(hold) = read_persistent::<Hold>(env, &DataKey::Hold(hold_id)) {
+            if hold.expires_at >= now {
+                total = total.saturating_add(hold.amount);
+            }
+        }
+    }
+    total
+}
 
+fn available_balance(env: &Env, user: &Address) -> i128 {
+    read_balance(env, user)
+        - held_amount(env, user)
+        - bucketed_amount(env, user)
+        - queued_amount(env, user)
+}
 
-        let mut b = balances(&env);
+/// Part of the balance of `user` reserved by queued withdrawals.
+fn queued_amount(env: &Env, user: &Address) -> i128 {
+    read_persistent(env, &DataKey::QueuedAmount(user.clone())).unwrap_or(0)
+}
 
-        let cur = b.get(user.clone()).unwrap_or(0);
+/// Storage key of the reservation a queued outflow makes: the asset
+/// balance for `Outflow::Asset`, the default balance otherwise.
+fn queued_key(user: &Address, outflow: &Outflow) -> DataKey {
+    match outflow {
+        Outflow::Asset(asset) => DataKey::QueuedAssetAmount(user.clone(), asset.clone()),
+        _ => DataKey::QueuedAmount(user.clone()),
+    }
+}
 
-        if cur < amount {
+fn reserve_queued(env: &Env, key: &DataKey, delta: i128) {
+    let amount = env.storage().persistent().get::<_, i128>(key).unwrap_or(0) + delta;
+    if amount == 0 {
+        env.storage().persistent().remove(key);
+    } else {
+        env.storage().persistent().set(key, &amount);
+        extend_persistent(env, key);
+    }
+}
 
-            panic!("insufficient balance")
+/// Queue config if an outflow of `amount` has to wait in the withdrawal
+/// queue, or `None` if it may run straight away.
+fn queue_config_for(env: &Env, amount: i128) -> Option<QueueConfig> {
+    env.storage()
+        .instance()
+        .get::<_, QueueConfig>(&DataKey::QueueConfig)
+        .filter(|config| amount > config.threshold)
+}
 
+/// Checks that `user` can cover `outflow` before anything is queued or the
+/// circuit breaker is consulted.
+fn require_outflow_funds(
+    env: &Env,
+    user: &Address,
+    amount: i128,
+    outflow: &Outflow,
+) -> Result<(), ContractError> {
+    require_not_frozen(env, user)?;
+    let funds = match outflow {
+        Outflow::Asset(asset) => {
+            read_asset_balance(env, user, asset) - queued_asset_amount(env, user, asset)
         }
+        Outflow::Bucket(bucket) => read_buckets(env, user).get(bucket.clone()).unwrap_or(0),
+        // The hold already reserves the funds.
+        Outflow::Capture(_) => return Ok(()),
+        // Withdrawals may only use funds the user owns.
+        Outflow::Withdraw(_) => available_balance(env, user),
+        // Admin debits may draw on the credit line.
+        Outflow::Debit(_) => spendable_balance(env, user),
+    };
+    if funds < amount {
+        return Err(ContractError::InsufficientBalance);
+    }
+    Ok(())
+}
 
-        b.set(user.clone(), cur - amount);
+/// Side effects an outflow has as soon as it is accepted, whether it runs
+/// now or waits in the queue.
+fn prepare_outflow(
+    env: &Env,
+    user: &Address,
+    amount: i128,
+    outflow: &Outflow,
+) -> Result<(), ContractError> {
+    match outflow {
+        Outflow::Debit(Some(reference)) => claim_reference(env, reference)?,
+        // Leaves the bucket now; a queued debit keeps the funds reserved and
+        // `cancel_withdrawal` puts them back.
+        Outflow::Bucket(bucket) => {
+            adjust_bucket(env, user, bucket, -amount)?;
+        }
+        // A queued capture reserves the funds itself, so the hold goes
+        // either way.
+        Outflow::Capture(hold_id) => {
+            let hold = read_hold(env, hold_id)?;
+            remove_hold(env, hold_id, &hold);
+        }
+        _ => {}
+    }
+    Ok(())
+}
 
-        put_balances(&env, b);
+/// Entry point for every admin debit and `withdraw`. Queues the outflow if
+/// it is above the queue threshold, stops it if it would trip the circuit
+/// breaker, and otherwise runs it.
+fn start_outflow(
+    env: &Env,
+    user: Address,
+    amount: i128,
+    outflow: Outflow,
+) -> Result<OutflowStatus, ContractError> {
+    require_outflow_funds(env, &user, amount, &outflow)?;
+    let queue = queue_config_for(env, amount);
+    if queue.is_none() && trip_breaker(env, amount, &outflow)? {
+        return Ok(OutflowStatus::Blocked);
+    }
+    prepare_outflow(env, &user, amount, &outflow)?;
+    match queue {
+        Some(config) => Ok(OutflowStatus::Queued(enqueue_withdrawal(
+            env, &user, amount, outflow, config,
+        ))),
+        None => {
+            run_outflow(env, user, amount, outflow)?;
+            Ok(OutflowStatus::Executed)
+        }
+    }
+}
 
+/// Stores an outflow in the withdrawal queue and reserves its amount.
+/// Returns the queue id.
+fn enqueue_withdrawal(
+    env: &Env,
+    user: &Address,
+    amount: i128,
+    outflow: Outflow,
+    config: QueueConfig,
+) -> u64 {
+    let id: u64 = env
+        .storage()
+        .instance()
+        .get(&DataKey::WithdrawalCount)
+        .unwrap_or(0);
+    let execute_after = env.ledger().timestamp().saturating_add(config.delay_secs);
+    reserve_queued(env, &queued_key(user, &outflow), amount);
+    let queued = QueuedWithdrawal {
+        user: user.clone(),
+        amount,
+        outflow,
+        guardian: config.guardian,
+        execute_after,
+    };
+    let key = DataKey::Withdrawal(id);
+    env.storage().persistent().set(&key, &queued);
+    extend_persistent(env, &key);
+    env.storage()
+        .instance()
+        .set(&DataKey::WithdrawalCount, &(id + 1));
 
+    env.events().publish(
+        (Symbol::new(env, "withdrawal_queued"), id, user.clone()),
+        (amount, queued.outflow, execute_after),
+    );
+    id
+}
 
-        env.events()
+fn read_withdrawal(env: &Env, id: u64) -> Result<QueuedWithdrawal, ContractError> {
+    read_persistent(env, &DataKey::Withdrawal(id)).ok_or(ContractError::WithdrawalNotFound)
+}
 
-            .publish((Symbol::new(&env, "debit"), user), amount);
+/// Drops a queued withdrawal and its reservation.
+fn dequeue_withdrawal(env: &Env, id: u64, queued: &QueuedWithdrawal) {
+    env.storage().persistent().remove(&DataKey::Withdrawal(id));
+    reserve_queued(env, &queued_key(&queued.user, &queued.outflow), -queued.amount);
+}
 
+/// Moves `amount` out of the balance of `user` as described by `outflow`
+/// and emits the event of the call that requested it. Shared by the entry
+/// points and `execute_withdrawal`.
+fn run_outflow(
+    env: &Env,
+    user: Address,
+    amount: i128,
+    outflow: Outflow,
+) -> Result<(), ContractError> {
+    match outflow {
+        Outflow::Debit(reference) => {
+            let (fee, net_amount, new_balance) =
+                apply_debit_with_fee(env, &user, amount, "debit", reference)?;
+            env.events().publish(
+                (Symbol::new(env, "debit"), user),
+                (amount, fee, net_amount, new_balance),
+            );
+        }
+        Outflow::Bucket(bucket) => {
+            let (fee, net_amount, _) = apply_debit_with_fee(env, &user, amount, "debit", None)?;
+            let bucket_balance = read_buckets(env, &user).get(bucket.clone()).unwrap_or(0);
+            env.events().publish(
+                (Symbol::new(env, "debit_bucket"), user, bucket),
+                (amount, fee, net_amount, bucket_balance),
+            );
+        }
+        Outflow::Capture(hold_id) => {
+            let (fee, net_amount, new_balance) =
+                apply_debit_with_fee(env, &user, amount, "capture", Some(hold_id.clone()))?;
+            env.events().publish(
+                (Symbol::new(env, "capture"), user),
+                (hold_id, amount, fee, net_amount, new_balance),
+            );
+        }
+        Outflow::Asset(asset) => {
+            require_not_frozen(env, &user)?;
+            let cur = read_asset_balance(env, &user, &asset);
+            if cur - queued_asset_amount(env, &user, &asset) < amount {
+                return Err(ContractError::InsufficientBalance);
+            }
+            consume_asset_debit_cap(env, &user, &asset, amount)?;
+            let new_balance = cur - amount;
+            write_asset_balance(env, &user, &asset, new_balance);
+            record_entry(env, &user, "debit", amount, new_balance, None, Some(asset.clone()));
+            env.events().publish(
+                (Symbol::new(env, "debit_asset"), user, asset),
+                (amount, new_balance),
+            );
+        }
+        Outflow::Withdraw(to) => {
+            let new_balance = pay_out(env, &user, amount, &to)?;
+            env.events().publish(
+                (Symbol::new(env, "withdraw"), user),
+                (amount, to, new_balance),
+            );
+        }
     }
+    Ok(())
+}
 
+/// Debits `amount` from `user` and sends the same amount of the configured
+/// token to `to`. Returns the new balance.
+fn pay_out(env: &Env, user: &Address, amount: i128, to: &Address) -> Result<i128, ContractError> {
+    let token = get_token(env)?;
+    // Only funds the user owns can leave as tokens, never drawn credit.
+    if available_balance(env, user) < amount {
+        return Err(ContractError::InsufficientBalance);
+    }
+    let new_balance = apply_debit(env, user, amount, "withdraw", None)?;
+    token::Client::new(env, &token).transfer(&env.current_contract_address(), to, &amount);
+    Ok(new_balance)
+}
 
+fn read_buckets(env: &Env, user: &Address) -> Map<Symbol, i128> {
+    read_persistent(env, &DataKey::Buckets(user.clone())).unwrap_or_else(|| Map::new(env))
+}
 
-    pub fn balance(env: Env, user: Address) -> i128 {
+fn write_buckets(env: &Env, user: &Address, buckets: &Map<Symbol, i128>) {
+    let key = DataKey::Buckets(user.clone());
+    if buckets.is_empty() {
+        env.storage().persistent().remove(&key);
+    } else {
+        env.storage().persistent().set(&key, buckets);
+        extend_persistent(env, &key);
+    }
+}
 
-        let b = balances(&env);
+/// Adds `delta` to one bucket of `user`, dropping buckets that reach zero.
+fn adjust_bucket(
+    env: &Env,
+    user: &Address,
+    bucket: &Symbol,
+    delta: i128,
+) -> Result<i128, ContractError> {
+    let mut buckets = read_buckets(env, user);
+    let new_amount = buckets
+        .get(bucket.clone())
+        .unwrap_or(0)
+        .checked_add(delta)
+        .ok_or(ContractError::Overflow)?;
+    if new_amount < 0 {
+        return Err(ContractError::InsufficientBalance);
+    }
+    if new_amount == 0 {
+        buckets.remove(bucket.clone());
+    } else {
+        buckets.set(bucket.clone(), new_amount);
+    }
+    write_buckets(env, user, &buckets);
+    Ok(new_amount)
+}
 
-        b.get(user).unwrap_or(0)
+/// Part of the balance of `user` earmarked in buckets.
+fn bucketed_amount(env: &Env, user: &Address) -> i128 {
+    read_buckets(env, user)
+        .values()
+        .iter()
+        .fold(0i128, |total, amount| total.saturating_add(amount))
+}
 
+fn require_named_bucket(bucket: &Symbol) -> Result<(), ContractError> {
+    if *bucket == MAIN_BUCKET {
+        return Err(ContractError::InvalidBucket);
     }
+    Ok(())
+}
 
+fn credit_limit(env: &Env, user: &Address) -> i128 {
+    read_persistent(env, &DataKey::CreditLimit(user.clone())).unwrap_or(0)
+}
 
+/// Available balance plus the credit line: how far a debit or hold may go.
+fn spendable_balance(env: &Env, user: &Address) -> i128 {
+    available_balance(env, user).saturating_add(credit_limit(env, user))
+}
 
-    pub fn set_admin(env: Env, new_admin: Address) {
+/// Fails with `DuplicateReference` if `reference` was already processed.
+fn require_unprocessed(env: &Env, reference: &BytesN<32>) -> Result<(), ContractError> {
+    if env
+        .storage()
+        .persistent()
+        .has(&DataKey::ProcessedRef(reference.clone()))
+    {
+        return Err(ContractError::DuplicateReference);
+    }
+    Ok(())
+}
 
-        require_admin(&env);
+/// Marks `reference` as processed, rejecting references seen before. Entries
+/// are kept alive for the same window as balances so retries within that
+/// window are always caught.
+fn claim_reference(env: &Env, reference: &BytesN<32>) -> Result<(), ContractError> {
+    require_unprocessed(env, reference)?;
+    let key = DataKey::ProcessedRef(reference.clone());
+    env.storage()
+        .persistent()
+        .set(&key, &env.ledger().timestamp());
+    extend_persistent(env, &key);
+    Ok(())
+}
 
-        env.storage().instance().set(&DataKey::Admin, &new_admin);
+fn journal_count(env: &Env, user: &Address) -> u64 {
+    read_persistent(env, &DataKey::JournalCount(user.clone())).unwrap_or(0)
+}
 
-        env.events()
+/// Appends an entry to the journal of `user`.
+fn record_entry(
+    env: &Env,
+    user: &Address,
+    kind: &str,
+    amount: i128,
+    balance: i128,
+    reference: Option<BytesN<32>>,
+    asset: Option<Address>,
+) {
+    let seq = journal_count(env, user);
+    let entry = JournalEntry {
+        seq,
+        kind: Symbol::new(env, kind),
+        amount,
+        balance,
+        timestamp: env.ledger().timestamp(),
+        reference,
+        asset,
+    };
+    let entry_key = DataKey::JournalEntry(user.clone(), seq);
+    let count_key = DataKey::JournalCount(user.clone());
+    env.storage().persistent().set(&entry_key, &entry);
+    env.storage().persistent().set(&count_key, &(seq + 1));
+    extend_persistent(env, &entry_key);
+    extend_persistent(env, &count_key);
+}
 
-            .publish((Symbol::new(&env, "set_admin"),), new_admin);
+fn read_totals(env: &Env) -> Totals {
+    env.storage()
+        .instance()
+        .get(&DataKey::Totals)
+        .unwrap_or_default()
+}
 
+/// Adds money entering (`credited`) or leaving (`debited`) the wallet to the
+/// running totals.
+fn update_totals(env: &Env, credited: i128, debited: i128) -> Result<(), ContractError> {
+    let mut totals = read_totals(env);
+    totals.credited = totals
+        .credited
+        .checked_add(credited)
+        .ok_or(ContractError::Overflow)?;
+    totals.debited = totals
+        .debited
+        .checked_add(debited)
+        .ok_or(ContractError::Overflow)?;
+    totals.balance = totals
+        .balance
+        .checked_add(credited - debited)
+        .ok_or(ContractError::Overflow)?;
+    env.storage().instance().set(&DataKey::Totals, &totals);
+    if debited > 0 {
+        record_outflow(env, debited)?;
     }
+    Ok(())
+}
 
+fn breaker_config(env: &Env) -> Option<BreakerConfig> {
+    env.storage().instance().get(&DataKey::BreakerConfig)
+}
 
-    pub fn pause(env: Env) {
-        require_admin(&env);
-        env.storage().instance().set(&DataKey::Paused, &true);
-        env.events().publish((Symbol::new(&env, "pause"),), ());
+/// Per-slot outflows still inside the breaker window, as `(slot, amount)`
+/// pairs, plus the current slot.
+fn live_outflow_slots(env: &Env, config: &BreakerConfig) -> (Vec<(u64, i128)>, u64) {
+    let slot_secs = config.window_secs / BREAKER_SLOTS;
+    let now_slot = env.ledger().timestamp() / slot_secs;
+    let stored: Vec<(u64, i128)> = env
+        .storage()
+        .instance()
+        .get(&DataKey::OutflowSlots)
+        .unwrap_or_else(|| Vec::new(env));
+    let mut live = Vec::new(env);
+    for (slot, amount) in stored.iter() {
+        if slot + BREAKER_SLOTS > now_slot {
+            live.push_back((slot, amount));
+        }
     }
+    (live, now_slot)
+}
 
-    pub fn unpause(env: Env) {
-        require_admin(&env);
-        env.storage().instance().set(&DataKey::Paused, &false);
-        env.events().publish((Symbol::new(&env, "unpause"),), ());
+fn window_outflow(env: &Env) -> i128 {
+    match breaker_config(env) {
+        Some(config) => live_outflow_slots(env, &config)
+            .0
+            .iter()
+            .map(|(_, amount)| amount)
+            .sum(),
+        None => 0,
     }
+}
 
-    pub fn is_paused(env: Env) -> bool {
-        get_paused_state(&env)
+/// Counts `amount` towards the breaker window.
+fn record_outflow(env: &Env, amount: i128) -> Result<(), ContractError> {
+    let Some(config) = breaker_config(env) else {
+        return Ok(());
+    };
+    let (mut slots, now_slot) = live_outflow_slots(env, &config);
+    match slots.last() {
+        Some((slot, current)) if slot == now_slot => {
+            let current = current.checked_add(amount).ok_or(ContractError::Overflow)?;
+            slots.set(slots.len() - 1, (slot, current));
+        }
+        _ => slots.push_back((now_slot, amount)),
     }
+    env.storage().instance().set(&DataKey::OutflowSlots, &slots);
+    Ok(())
 }
 
+/// Checks whether running `outflow` would take the breaker window over the
+/// threshold. If so, pauses debits and withdrawals, starts a fresh window,
+/// emits a `circuit_breaker` event and returns `true`. The caller then
+/// returns `OutflowStatus::Blocked` without moving any funds; returning an
+/// error instead would roll back the pause.
+fn trip_breaker(env: &Env, amount: i128, outflow: &Outflow) -> Result<bool, ContractError> {
+    let Some(config) = breaker_config(env) else {
+        return Ok(false);
+    };
+    // Count what `update_totals` will record: the net amount for debits that
+    // pay a fee, nothing for non-default assets. The window is a single
+    // default-asset total, so other assets rely on their own debit caps.
+    let counted = match outflow {
+        Outflow::Asset(_) => return Ok(false),
+        Outflow::Withdraw(_) => amount,
+        _ => match fee_config(env) {
+            Some(fee) => amount - compute_fee(&fee, amount)?,
+            None => amount,
+        },
+    };
+    let total = window_outflow(env)
+        .checked_add(counted)
+        .ok_or(ContractError::Overflow)?;
+    if total <= config.threshold {
+        return Ok(false);
+    }
 
+    env.storage().instance().remove(&DataKey::OutflowSlots);
+    set_pause_flags(env, pause_flags(env) | PAUSE_DEBIT | PAUSE_WITHDRAW)?;
+    env.events().publish(
+        (Symbol::new(env, "circuit_breaker"),),
+        (total, config.threshold, config.window_secs),
+    );
+    Ok(true)
+}
 
-#[cfg(test)]
-
-mod test {
+/// Adds `amount` to the internal balance of `user`, journals it under `kind`,
+/// counts it in the running totals and returns the new balance.
+fn apply_credit(
+    env: &Env,
+    user: &Address,
+    amount: i128,
+    kind: &str,
+    reference: Option<BytesN<32>>,
+) -> Result<i128, ContractError> {
+    let new_balance = credit_balance(env, user, amount, kind, reference)?;
+    update_totals(env, amount, 0)?;
+    Ok(new_balance)
+}
 
-    extern crate std;
+/// Removes `amount` from the internal balance of `user`, journals it under
+/// `kind`, counts it in the running totals and returns the new balance.
+fn apply_debit(
+    env: &Env,
+    user: &Address,
+    amount: i128,
+    kind: &str,
+    reference: Option<BytesN<32>>,
+) -> Result<i128, ContractError> {
+    let new_balance = debit_balance(env, user, amount, kind, reference, true)?;
+    update_totals(env, 0, amount)?;
+    Ok(new_balance)
+}
 
+fn fee_config(env: &Env) -> Option<FeeConfig> {
+    env.storage().instance().get(&DataKey::FeeConfig)
+}
 
+/// Fee owed on a debit of `amount`. Never more than `amount` itself.
+fn compute_fee(config: &FeeConfig, amount: i128) -> Result<i128, ContractError> {
+    let proportional = amount
+        .checked_mul(config.bps as i128)
+        .ok_or(ContractError::Overflow)?
+        / 10_000;
+    Ok(proportional.max(config.min_fee).min(amount))
+}
 
-    use super::{RentWallet, RentWalletClient};
-    use soroban_sdk::testutils::{Address as _, MockAuth, MockAuthInvoke, Events};
-    use soroban_sdk::{Address, Env, IntoVal, Symbol, TryIntoVal};
+/// Debits `amount` from `user`, journalled under `kind`, and moves the
+/// platform fee, if one is configured, to the treasury balance. Only the net
+/// amount counts as leaving the wallet. Returns `(fee, net_amount,
+/// new_balance)`.
+fn apply_debit_with_fee(
+    env: &Env,
+    user: &Address,
+    amount: i128,
+    kind: &str,
+    reference: Option<BytesN<32>>,
+) -> Result<(i128, i128, i128), ContractError> {
+    let (fee, treasury) = match fee_config(env) {
+        Some(config) => (compute_fee(&config, amount)?, Some(config.treasury)),
+        None => (0, None),
+    };
+    let new_balance = debit_balance(env, user, amount, kind, reference.clone(), true)?;
+    if let Some(treasury) = treasury.filter(|_| fee > 0) {
+        // Freezing the treasury stops it paying out, not collecting fees,
+        // so it cannot block every debit.
+        add_to_balance(env, &treasury, fee, "fee", reference)?;
+    }
+    let net_amount = amount - fee;
+    update_totals(env, 0, net_amount)?;
+    Ok((fee, net_amount, new_balance))
+}
+
+/// Credit leg shared by `apply_credit` and transfers, which move money
+/// within the wallet and so leave the totals alone.
+fn credit_balance(
+    env: &Env,
+    user: &Address,
+    amount: i128,
+    kind: &str,
+    reference: Option<BytesN<32>>,
+) -> Result<i128, ContractError> {
+    require_not_frozen(env, user)?;
+    add_to_balance(env, user, amount, kind, reference)
+}
+
+/// `credit_balance` without the freeze check.
+fn add_to_balance(
+    env: &Env,
+    user: &Address,
+    amount: i128,
+    kind: &str,
+    reference: Option<BytesN<32>>,
+) -> Result<i128, ContractError> {
+    let cur = read_balance(env, user);
+    let new_balance = cur.checked_add(amount).ok_or(ContractError::Overflow)?;
+    write_balance(env, user, new_balance)?;
+    record_entry(env, user, kind, amount, new_balance, reference, None);
+    if cur < 0 {
+        // A negative balance is drawn credit, so credits repay it first.
+        let repaid = amount.min(-cur);
+        env.events().publish(
+            (Symbol::new(env, "credit_repaid"), user.clone()),
+            (repaid, (-new_balance).max(0)),
+        );
+    }
+    Ok(new_balance)
+}
+
+/// Debit leg shared by `apply_debit` and transfers. Funds reserved by active
+/// holds cannot be debited. With `use_credit` the balance may go negative
+/// down to the user's credit limit; only admin outflows pass it, so drawn
+/// credit never moves to another wallet balance.
+fn debit_balance(
+    env: &Env,
+    user: &Address,
+    amount: i128,
+    kind: &str,
+    reference: Option<BytesN<32>>,
+    use_credit: bool,
+) -> Result<i128, ContractError> {
+    require_not_frozen(env, user)?;
+    let funds = if use_credit {
+        spendable_balance(env, user)
+    } else {
+        available_balance(env, user)
+    };
+    if funds < amount {
+        return Err(ContractError::InsufficientBalance);
+    }
+    consume_debit_cap(env, user, amount)?;
+    let cur = read_balance(env, user);
+    let new_balance = cur.checked_sub(amount).ok_or(ContractError::Overflow)?;
+    write_balance(env, user, new_balance)?;
+    record_entry(env, user, kind, amount, new_balance, reference, None);
+    Ok(new_balance)
+}
+
+fn supported_assets(env: &Env) -> Vec<Address> {
+    env.storage()
+        .instance()
+        .get(&DataKey::Assets)
+        .unwrap_or_else(|| Vec::new(env))
+}
+
+/// Whether `asset` is the default asset configured with `set_token`.
+fn is_default_asset(env: &Env, asset: &Address) -> bool {
+    env.storage()
+        .instance()
+        .get::<_, Address>(&DataKey::Token)
+        .is_some_and(|token| &token == asset)
+}
+
+fn read_asset_balance(env: &Env, user: &Address, asset: &Address) -> i128 {
+    let key = DataKey::AssetBalance(user.clone(), asset.clone());
+    match env.storage().persistent().get::<_, i128>(&key) {
+        Some(balance) => {
+            extend_persistent(env, &key);
+            balance
+        }
+        None => 0,
+    }
+}
+
+/// Part of the `asset` balance of `user` reserved by queued withdrawals.
+fn queued_asset_amount(env: &Env, user: &Address, asset: &Address) -> i128 {
+    read_persistent(env, &DataKey::QueuedAssetAmount(user.clone(), asset.clone())).unwrap_or(0)
+}
+
+fn write_asset_balance(env: &Env, user: &Address, asset: &Address, balance: i128) {
+    let key = DataKey::AssetBalance(user.clone(), asset.clone());
+    env.storage().persistent().set(&key, &balance);
+    extend_persistent(env, &key);
+    index_account(env, user);
+    extend_account_index(env, user);
+}
+
+fn read_pending_admin(env: &Env) -> Result<PendingAdmin, ContractError> {
+    env.storage()
+        .instance()
+        .get(&DataKey::PendingAdmin)
+        .ok_or(ContractError::NoPendingAdmin)
+}
+
+/// Allowance of `spender` over `owner`; zero once the expiration ledger has
+/// passed.
+fn read_allowance(env: &Env, owner: &Address, spender: &Address) -> i128 {
+    let key = DataKey::Allowance(owner.clone(), spender.clone());
+    match env.storage().temporary().get::<_, Allowance>(&key) {
+        Some(allowance) if allowance.expiration_ledger >= env.ledger().sequence() => {
+            allowance.amount
+        }
+        _ => 0,
+    }
+}
+
+/// Stores an allowance in temporary storage, living until its expiration
+/// ledger like SEP-41 token allowances.
+fn write_allowance(
+    env: &Env,
+    owner: &Address,
+    spender: &Address,
+    amount: i128,
+    expiration_ledger: u32,
+) -> Result<(), ContractError> {
+    if amount > 0 && expiration_ledger < env.ledger().sequence() {
+        return Err(ContractError::InvalidExpiry);
+    }
+    let key = DataKey::Allowance(owner.clone(), spender.clone());
+    let allowance = Allowance {
+        amount,
+        expiration_ledger,
+    };
+    env.storage().temporary().set(&key, &allowance);
+    if amount > 0 {
+        let live_for = expiration_ledger - env.ledger().sequence();
+        env.storage().temporary().extend_ttl(&key, live_for, live_for);
+    }
+    Ok(())
+}
+
+fn read_standing_order(env: &Env, order_id: u64) -> Result<StandingOrder, ContractError> {
+    read_persistent(env, &DataKey::StandingOrder(order_id))
+        .ok_or(ContractError::StandingOrderNotFound)
+}
+
+/// Stores `order`, or removes it once no runs are left.
+fn write_standing_order(env: &Env, order_id: u64, order: &StandingOrder) {
+    let key = DataKey::StandingOrder(order_id);
+    if order.remaining == 0 {
+        env.storage().persistent().remove(&key);
+    } else {
+        env.storage().persistent().set(&key, order);
+        extend_persistent(env, &key);
+    }
+}
+
+#[contractimpl]
+impl RentWallet {
+    pub fn init(env: Env, admin: Address) -> Result<(), ContractError> {
+        if env.storage().instance().has(&DataKey::Admin) {
+            return Err(ContractError::AlreadyInitialized);
+        }
+        env.storage().instance().set(&DataKey::Admin, &admin);
+        env.storage()
+            .instance()
+            .set(&DataKey::Version, &SCHEMA_VERSION);
+        extend_instance_ttl(&env);
+
+        env.events().publish((Symbol::new(&env, "init"),), admin);
+        Ok(())
+    }
+
+    pub fn credit(env: Env, user: Address, amount: i128) -> Result<(), ContractError> {
+        require_admin(&env)?;
+        require_not_paused(&env, PAUSE_CREDIT)?;
+        require_positive(amount)?;
+
+        apply_credit(&env, &user, amount, "credit", None)?;
+
+        env.events()
+            .publish((Symbol::new(&env, "credit"), user), amount);
+        Ok(())
+    }
+
+    /// Credits `user` on behalf of a registered operator. Operators may only
+    /// credit; debits and role management stay with the admin.
+    pub fn credit_as_operator(
+        env: Env,
+        operator: Address,
+        user: Address,
+        amount: i128,
+    ) -> Result<(), ContractError> {
+        require_operator(&env, &operator)?;
+        require_not_paused(&env, PAUSE_CREDIT)?;
+        require_positive(amount)?;
+
+        apply_credit(&env, &user, amount, "credit", None)?;
+
+        env.events()
+            .publish((Symbol::new(&env, "credit"), user), amount);
+        Ok(())
+    }
+
+    pub fn debit(env: Env, user: Address, amount: i128) -> Result<OutflowStatus, ContractError> {
+        require_admin(&env)?;
+        require_not_paused(&env, PAUSE_DEBIT)?;
+        require_positive(amount)?;
+        start_outflow(&env, user, amount, Outflow::Debit(None))
+    }
+
+    /// Like `credit`, but keyed by an external reference (e.g. an outbox
+    /// message id). A replayed reference fails with `DuplicateReference`, so
+    /// retries can never credit twice.
+    pub fn credit_with_ref(
+        env: Env,
+        user: Address,
+        amount: i128,
+        reference: BytesN<32>,
+    ) -> Result<(), ContractError> {
+        require_admin(&env)?;
+        require_not_paused(&env, PAUSE_CREDIT)?;
+        require_positive(amount)?;
+        claim_reference(&env, &reference)?;
+
+        apply_credit(&env, &user, amount, "credit", Some(reference))?;
+
+        env.events()
+            .publish((Symbol::new(&env, "credit"), user), amount);
+        Ok(())
+    }
+
+    /// Like `debit`, but keyed by an external reference. A replayed reference
+    /// fails with `DuplicateReference`.
+    pub fn debit_with_ref(
+        env: Env,
+        user: Address,
+        amount: i128,
+        reference: BytesN<32>,
+    ) -> Result<OutflowStatus, ContractError> {
+        require_admin(&env)?;
+        require_not_paused(&env, PAUSE_DEBIT)?;
+        require_positive(amount)?;
+        require_unprocessed(&env, &reference)?;
+
+        start_outflow(&env, user, amount, Outflow::Debit(Some(reference)))
+    }
+
+    /// Credits `amount` of `asset` to `user`. The default asset goes through
+    /// `credit`; any other asset must be in the supported list.
+    pub fn credit_asset(
+        env: Env,
+        user: Address,
+        asset: Address,
+        amount: i128,
+    ) -> Result<(), ContractError> {
+        if is_default_asset(&env, &asset) {
+            return Self::credit(env, user, amount);
+        }
+        require_admin(&env)?;
+        require_not_paused(&env, PAUSE_CREDIT)?;
+        require_positive(amount)?;
+        if !supported_assets(&env).contains(&asset) {
+            return Err(ContractError::UnsupportedToken);
+        }
+        require_not_frozen(&env, &user)?;
+
+        let new_balance = read_asset_balance(&env, &user, &asset)
+            .checked_add(amount)
+            .ok_or(ContractError::Overflow)?;
+        write_asset_balance(&env, &user, &asset, new_balance);
+        record_entry(&env, &user, "credit", amount, new_balance, None, Some(asset.clone()));
+
+        env.events().publish(
+            (Symbol::new(&env, "credit_asset"), user, asset),
+            (amount, new_balance),
+        );
+        Ok(())
+    }
+
+    /// Debits `amount` of `asset` from `user`. The default asset goes through
+    /// `debit`. Other assets can still be debited after being removed from the
+    /// supported list, so remaining balances can be settled.
+    pub fn debit_asset(
+        env: Env,
+        user: Address,
+        asset: Address,
+        amount: i128,
+    ) -> Result<OutflowStatus, ContractError> {
+        if is_default_asset(&env, &asset) {
+            return Self::debit(env, user, amount);
+        }
+        require_admin(&env)?;
+        require_not_paused(&env, PAUSE_DEBIT)?;
+        require_positive(amount)?;
+
+        start_outflow(&env, user, amount, Outflow::Asset(asset))
+    }
+
+    pub fn balance_asset(env: Env, user: Address, asset: Address) -> i128 {
+        if is_default_asset(&env, &asset) {
+            return read_balance(&env, &user);
+        }
+        read_asset_balance(&env, &user, &asset)
+    }
+
+    /// Adds `asset` to the supported list. Adding an asset twice is a no-op.
+    pub fn add_asset(env: Env, asset: Address) -> Result<(), ContractError> {
+        require_admin(&env)?;
+        let mut assets = supported_assets(&env);
+        if !assets.contains(&asset) {
+            assets.push_back(asset.clone());
+            env.storage().instance().set(&DataKey::Assets, &assets);
+        }
+        env.events().publish((Symbol::new(&env, "add_asset"),), asset);
+        Ok(())
+    }
+
+    /// Removes `asset` from the supported list. Existing balances stay and can
+    /// still be debited.
+    pub fn remove_asset(env: Env, asset: Address) -> Result<(), ContractError> {
+        require_admin(&env)?;
+        let mut assets = supported_assets(&env);
+        if let Some(index) = assets.first_index_of(&asset) {
+            assets.remove(index);
+            env.storage().instance().set(&DataKey::Assets, &assets);
+        }
+        env.events()
+            .publish((Symbol::new(&env, "remove_asset"),), asset);
+        Ok(())
+    }
+
+    /// Returns the supported assets besides the default asset.
+    pub fn supported_assets(env: Env) -> Vec<Address> {
+        supported_assets(&env)
+    }
+
+    pub fn is_processed(env: Env, reference: BytesN<32>) -> bool {
+        env.storage()
+            .persistent()
+            .has(&DataKey::ProcessedRef(reference))
+    }
+
+    /// Returns the full balance of `user`, including funds reserved by holds.
+    pub fn balance(env: Env, user: Address) -> i128 {
+        read_balance(&env, &user)
+    }
+
+    /// Sum of all user balances.
+    pub fn total_balance(env: Env) -> i128 {
+        read_totals(&env).balance
+    }
+
+    /// Everything ever credited into the wallet, excluding transfers.
+    pub fn total_credited(env: Env) -> i128 {
+        read_totals(&env).credited
+    }
+
+    /// Everything ever debited out of the wallet, excluding transfers.
+    pub fn total_debited(env: Env) -> i128 {
+        read_totals(&env).debited
+    }
+
+    /// Checks the running totals against each other and, once a token is
+    /// configured, checks that the token balance the contract actually holds
+    /// covers every positive balance.
+    pub fn check_invariants(env: Env) -> InvariantReport {
+        let totals = read_totals(&env);
+        let owed = owed(&env);
+        let totals_consistent = totals.credited.checked_sub(totals.debited) == Some(totals.balance);
+        let token_holdings = env
+            .storage()
+            .instance()
+            .get::<_, Address>(&DataKey::Token)
+            .map(|token| {
+                token::Client::new(&env, &token).balance(&env.current_contract_address())
+            });
+        InvariantReport {
+            fully_backed: token_holdings.map(|held| held >= owed),
+            totals,
+            totals_consistent,
+            owed,
+            token_holdings,
+        }
+    }
+
+    /// Credits `amount` to `user` and earmarks it in `bucket`.
+    pub fn credit_bucket(
+        env: Env,
+        user: Address,
+        bucket: Symbol,
+        amount: i128,
+    ) -> Result<(), ContractError> {
+        require_admin(&env)?;
+        require_not_paused(&env, PAUSE_CREDIT)?;
+        require_positive(amount)?;
+        require_named_bucket(&bucket)?;
+
+        apply_credit(&env, &user, amount, "credit", None)?;
+        let bucket_balance = adjust_bucket(&env, &user, &bucket, amount)?;
+
+        env.events().publish(
+            (Symbol::new(&env, "credit_bucket"), user, bucket),
+            (amount, bucket_balance),
+        );
+        Ok(())
+    }
+
+    /// Debits `amount` from `bucket` of `user`. Only funds earmarked in that
+    /// bucket can be used; the platform fee applies as for `debit`.
+    pub fn debit_bucket(
+        env: Env,
+        user: Address,
+        bucket: Symbol,
+        amount: i128,
+    ) -> Result<OutflowStatus, ContractError> {
+        require_admin(&env)?;
+        require_not_paused(&env, PAUSE_DEBIT)?;
+        require_positive(amount)?;
+        require_named_bucket(&bucket)?;
+
+        start_outflow(&env, user, amount, Outflow::Bucket(bucket))
+    }
+
+    /// Moves `amount` between two buckets of `user`. `MAIN_BUCKET` on either
+    /// side stands for the unallocated balance. Signed by `user`.
+    pub fn move_between_buckets(
+        env: Env,
+        user: Address,
+        from: Symbol,
+        to: Symbol,
+        amount: i128,
+    ) -> Result<(), ContractError> {
+        user.require_auth();
+        require_positive(amount)?;
+        require_not_frozen(&env, &user)?;
+        if from == to {
+            return Err(ContractError::InvalidBucket);
+        }
+
+        if from == MAIN_BUCKET {
+            if available_balance(&env, &user) < amount {
+                return Err(ContractError::InsufficientBalance);
+            }
+        } else {
+            adjust_bucket(&env, &user, &from, -amount)?;
+        }
+        if to != MAIN_BUCKET {
+            adjust_bucket(&env, &user, &to, amount)?;
+        }
+
+        env.events().publish(
+            (Symbol::new(&env, "move_between_buckets"), user),
+            (from, to, amount),
+        );
+        Ok(())
+    }
+
+    pub fn bucket_balance(env: Env, user: Address, bucket: Symbol) -> i128 {
+        read_buckets(&env, &user).get(bucket).unwrap_or(0)
+    }
+
+    /// Every non-empty bucket of `user`.
+    pub fn buckets(env: Env, user: Address) -> Map<Symbol, i128> {
+        read_buckets(&env, &user)
+    }
+
+    /// Returns the part of the balance not reserved by active holds or
+    /// earmarked in buckets.
+    pub fn available_balance(env: Env, user: Address) -> i128 {
+        available_balance(&env, &user)
+    }
+
+    /// Lists the journal of `user` oldest first with cursor-based pagination.
+    ///
+    /// # Arguments
+    /// * `user` - The account to list entries for
+    /// * `cursor` - `None` for the first page, otherwise `next_cursor` of the
+    ///   previous page (the `seq` of the last entry returned)
+    /// * `limit` - Maximum number of entries to return (must be > 0 and <= 100)
+    pub fn list_entries(
+        env: Env,
+        user: Address,
+        cursor: Option<u64>,
+        limit: u32,
+    ) -> Result<JournalPage, ContractError> {
+        if limit == 0 || limit > MAX_PAGE_LIMIT {
+            return Err(ContractError::InvalidLimit);
+        }
+
+        let count = journal_count(&env, &user);
+        let start = cursor.map_or(0, |c| c.saturating_add(1)).min(count);
+        let end = start.saturating_add(limit as u64).min(count);
+
+        let mut entries = Vec::new(&env);
+        for seq in start..end {
+            if let Some(entry) = read_persistent(&env, &DataKey::JournalEntry(user.clone(), seq)) {
+                entries.push_back(entry);
+            }
+        }
+
+        let has_next = end < count;
+        Ok(JournalPage {
+            entries,
+            has_next,
+            next_cursor: if has_next { end - 1 } else { 0 },
+        })
+    }
+
+    pub fn entry_count(env: Env, user: Address) -> u64 {
+        journal_count(&env, &user)
+    }
+
+    /// Lists every account that has held a balance, with its current
+    /// balance, in the order accounts first appeared.
+    ///
+    /// # Arguments
+    /// * `cursor` - `None` for the first page, otherwise `next_cursor` of the
+    ///   previous page
+    /// * `limit` - Maximum number of accounts to return (must be > 0 and <= 100)
+    pub fn list_accounts(
+        env: Env,
+        cursor: Option<u64>,
+        limit: u32,
+    ) -> Result<AccountPage, ContractError> {
+        if limit == 0 || limit > MAX_PAGE_LIMIT {
+            return Err(ContractError::InvalidLimit);
+        }
+
+        let count = account_count(&env);
+        let start = cursor.map_or(0, |c| c.saturating_add(1)).min(count);
+        let end = start.saturating_add(limit as u64).min(count);
+
+        let mut accounts = Vec::new(&env);
+        for position in start..end {
+            if let Some(user) = env
+                .storage()
+                .persistent()
+                .get::<_, Address>(&DataKey::AccountAt(position))
+            {
+                let balance = read_balance(&env, &user);
+                accounts.push_back((user, balance));
+            }
+        }
+
+        let has_next = end < count;
+        Ok(AccountPage {
+            accounts,
+            has_next,
+            next_cursor: if has_next { end - 1 } else { 0 },
+        })
+    }
+
+    /// Number of accounts in the index used by `list_accounts`.
+    pub fn account_count(env: Env) -> u64 {
+        account_count(&env)
+    }
+
+    /// Reserves `amount` of the available balance of `user` until `expires_at`.
+    /// Held funds stay in `balance` but cannot be debited until the hold is
+    /// captured, released or expires.
+    pub fn hold(
+        env: Env,
+        user: Address,
+        amount: i128,
+        hold_id: BytesN<32>,
+        expires_at: u64,
+    ) -> Result<(), ContractError> {
+        require_admin(&env)?;
+        require_not_paused(&env, PAUSE_DEBIT)?;
+        require_positive(amount)?;
+        if expires_at <= env.ledger().timestamp() {
+            return Err(ContractError::InvalidExpiry);
+        }
+        let key = DataKey::Hold(hold_id.clone());
+        if env.storage().persistent().has(&key) {
+            return Err(ContractError::DuplicateHold);
+        }
+        require_not_frozen(&env, &user)?;
+        prune_expired_holds(&env, &user);
+        if spendable_balance(&env, &user) < amount {
+            return Err(ContractError::InsufficientBalance);
+        }
+
+        let hold = Hold {
+            user: user.clone(),
+            amount,
+            expires_at,
+        };
+        env.storage().persistent().set(&key, &hold);
+        extend_persistent(&env, &key);
+        let mut holds = read_user_holds(&env, &user);
+        holds.push_back(hold_id.clone());
+        write_user_holds(&env, &user, &holds);
+
+        env.events().publish(
+            (Symbol::new(&env, "hold"), user),
+            (hold_id, amount, expires_at),
+        );
+        Ok(())
+    }
+
+    /// Debits `amount` of a hold from the user's balance and releases the
+    /// rest of the hold.
+    pub fn capture(
+        env: Env,
+        hold_id: BytesN<32>,
+        amount: i128,
+    ) -> Result<OutflowStatus, ContractError> {
+        require_admin(&env)?;
+        require_not_paused(&env, PAUSE_DEBIT)?;
+        require_positive(amount)?;
+        let hold = read_hold(&env, &hold_id)?;
+        if hold.expires_at < env.ledger().timestamp() {
+            return Err(ContractError::HoldExpired);
+        }
+        if amount > hold.amount {
+            return Err(ContractError::CaptureExceedsHold);
+        }
+
+        start_outflow(&env, hold.user, amount, Outflow::Capture(hold_id))
+    }
+
+    /// Cancels a hold, returning its funds to the available balance. Also
+    /// cleans up holds that have already expired.
+    pub fn release(env: Env, hold_id: BytesN<32>) -> Result<(), ContractError> {
+        require_admin(&env)?;
+        let hold = read_hold(&env, &hold_id)?;
+
+        remove_hold(&env, &hold_id, &hold);
+
+        env.events().publish(
+            (Symbol::new(&env, "release"), hold.user),
+            (hold_id, hold.amount),
+        );
+        Ok(())
+    }
+
+    pub fn get_hold(env: Env, hold_id: BytesN<32>) -> Option<Hold> {
+        env.storage().persistent().get(&DataKey::Hold(hold_id))
+    }
+
+    /// Credits `amount` to `user` locked until `unlock_at`. The funds do not
+    /// count as spendable until claimed with `claim_unlocked`.
+    pub fn credit_locked(
+        env: Env,
+        user: Address,
+        amount: i128,
+        unlock_at: u64,
+    ) -> Result<(), ContractError> {
+        require_admin(&env)?;
+        require_not_paused(&env, PAUSE_CREDIT)?;
+        add_locked_grant(&env, &user, amount, unlock_at, unlock_at)?;
+
+        env.events().publish(
+            (Symbol::new(&env, "credit_locked"), user),
+            (amount, unlock_at),
+        );
+        Ok(())
+    }
+
+    /// Credits `amount` to `user` vesting linearly between `start` and `end`.
+    pub fn credit_vesting(
+        env: Env,
+        user: Address,
+        amount: i128,
+        start: u64,
+        end: u64,
+    ) -> Result<(), ContractError> {
+        require_admin(&env)?;
+        require_not_paused(&env, PAUSE_CREDIT)?;
+        if start >= end {
+            return Err(ContractError::InvalidSchedule);
+        }
+        add_locked_grant(&env, &user, amount, start, end)?;
+
+        env.events().publish(
+            (Symbol::new(&env, "credit_vesting"), user),
+            (amount, start, end),
+        );
+        Ok(())
+    }
+
+    /// Moves every vested, unclaimed grant amount of `user` into the
+    /// spendable balance and returns the amount moved.
+    pub fn claim_unlocked(env: Env, user: Address) -> Result<i128, ContractError> {
+        user.require_auth();
+        require_not_paused(&env, PAUSE_CREDIT)?;
+        let now = env.ledger().timestamp();
+
+        let mut claimed = 0i128;
+        let mut remaining = Vec::new(&env);
+        for mut grant in read_locked_grants(&env, &user).iter() {
+            let vested = vested_amount(&grant, now);
+            claimed = claimed
+                .checked_add(vested - grant.claimed)
+                .ok_or(ContractError::Overflow)?;
+            grant.claimed = vested;
+            if grant.claimed < grant.amount {
+                remaining.push_back(grant);
+            }
+        }
+        if claimed == 0 {
+            return Ok(0);
+        }
+        write_locked_grants(&env, &user, &remaining);
+        let new_balance = apply_credit(&env, &user, claimed, "unlock", None)?;
+
+        env.events().publish(
+            (Symbol::new(&env, "claim_unlocked"), user),
+            (claimed, new_balance),
+        );
+        Ok(claimed)
+    }
+
+    pub fn locked_grants(env: Env, user: Address) -> Vec<LockedGrant> {
+        read_locked_grants(&env, &user)
+    }
+
+    /// Splits the funds of `user` into the spendable balance, still-locked
+    /// grants and vested grants waiting to be claimed.
+    pub fn balance_details(env: Env, user: Address) -> Result<BalanceDetails, ContractError> {
+        let now = env.ledger().timestamp();
+        let mut locked = 0i128;
+        let mut claimable = 0i128;
+        for grant in read_locked_grants(&env, &user).iter() {
+            let vested = vested_amount(&grant, now);
+            locked = locked.saturating_add(grant.amount - vested);
+            claimable = claimable.saturating_add(vested - grant.claimed);
+        }
+        Ok(BalanceDetails {
+            spendable: read_balance(&env, &user),
+            locked,
+            claimable,
+        })
+    }
+
+    /// Moves balances left in the legacy instance-storage map into per-user
+    /// persistent entries and deletes the map. Returns the number of accounts
+    /// migrated. Can only run once, since the map no longer exists afterwards.
+    pub fn migrate_balances(env: Env) -> Result<u32, ContractError> {
+        require_admin(&env)?;
+        let moved = move_legacy_balances(&env)?;
+
+        env.events()
+            .publish((Symbol::new(&env, "migrate_balances"),), moved);
+        Ok(moved)
+    }
+
+    /// Replaces the contract code with the already-uploaded `new_wasm_hash`.
+    /// Storage is kept as is; call `migrate()` afterwards if the new build
+    /// bumps `SCHEMA_VERSION`.
+    pub fn upgrade(env: Env, new_wasm_hash: BytesN<32>) -> Result<(), ContractError> {
+        require_admin(&env)?;
+        env.deployer()
+            .update_current_contract_wasm(new_wasm_hash.clone());
+
+        env.events()
+            .publish((Symbol::new(&env, "upgrade"),), new_wasm_hash);
+        Ok(())
+    }
+
+    /// Runs every data transform between the stored schema version and
+    /// `SCHEMA_VERSION`, then records the new version. Each step runs once.
+    pub fn migrate(env: Env) -> Result<u32, ContractError> {
+        require_admin(&env)?;
+        let from = schema_version(&env);
+        if from >= SCHEMA_VERSION {
+            return Err(ContractError::AlreadyMigrated);
+        }
+
+        // v1: balances live in per-user persistent entries.
+        if from < 1 {
+            match move_legacy_balances(&env) {
+                Ok(_) | Err(ContractError::NoLegacyBalances) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        // v2: pause state is a bitmask instead of a boolean.
+        if from < 2 {
+            let flags = pause_flags(&env);
+            env.storage().instance().set(&DataKey::PauseFlags, &flags);
+            env.storage().instance().remove(&DataKey::Paused);
+        }
+
+        env.storage()
+            .instance()
+            .set(&DataKey::Version, &SCHEMA_VERSION);
+        env.events()
+            .publish((Symbol::new(&env, "migrate"),), (from, SCHEMA_VERSION));
+        Ok(SCHEMA_VERSION)
+    }
+
+    /// Returns the stored storage schema version.
+    pub fn version(env: Env) -> u32 {
+        schema_version(&env)
+    }
+
+    /// Sets the TTL policy used for the instance and every persistent entry.
+    /// Takes effect the next time each entry is touched.
+    pub fn set_ttl_config(env: Env, config: TtlConfig) -> Result<(), ContractError> {
+        require_admin(&env)?;
+        if config.threshold > config.extend_to || config.extend_to > env.storage().max_ttl() {
+            return Err(ContractError::InvalidTtlConfig);
+        }
+        env.storage().instance().set(&DataKey::TtlConfig, &config);
+        env.events().publish(
+            (Symbol::new(&env, "ttl_config"),),
+            (config.threshold, config.extend_to),
+        );
+        Ok(())
+    }
+
+    pub fn ttl_config(env: Env) -> TtlConfig {
+        ttl_config(&env)
+    }
+
+    /// Extends the contract instance, the balance of `user` and the user's
+    /// other entries. Anyone may call this to keep a quiet account from being
+    /// archived.
+    pub fn bump(env: Env, user: Address) {
+        extend_instance_ttl(&env);
+        extend_balance_ttl(&env, &user);
+        extend_user_entries(&env, &user);
+    }
+
+    /// Ledgers left before the contract instance expires, as last recorded by
+    /// the contract. The real TTL is never lower than this.
+    pub fn instance_ttl(env: Env) -> u32 {
+        tracked_live_until(&env, &DataKey::InstanceLiveUntil)
+            .saturating_sub(env.ledger().sequence())
+    }
+
+    /// Ledgers left before the balance entry of `user` expires; 0 if the
+    /// user has no balance entry.
+    pub fn balance_ttl(env: Env, user: Address) -> u32 {
+        tracked_live_until(&env, &DataKey::BalanceLiveUntil(user))
+            .saturating_sub(env.ledger().sequence())
+    }
+
+    /// Starts an admin handover. The current admin stays in control until
+    /// `new_admin` calls `accept_admin`. Replaces any pending proposal.
+    pub fn propose_admin(
+        env: Env,
+        new_admin: Address,
+        expires_at: Option<u64>,
+    ) -> Result<(), ContractError> {
+        require_admin(&env)?;
+        if let Some(expires_at) = expires_at {
+            if expires_at <= env.ledger().timestamp() {
+                return Err(ContractError::InvalidExpiry);
+            }
+        }
+        let pending = PendingAdmin {
+            new_admin: new_admin.clone(),
+            expires_at,
+        };
+        env.storage().instance().set(&DataKey::PendingAdmin, &pending);
+        env.events().publish(
+            (Symbol::new(&env, "propose_admin"),),
+            (new_admin, expires_at),
+        );
+        Ok(())
+    }
+
+    /// Completes a handover. Must be signed by the proposed admin.
+    pub fn accept_admin(env: Env) -> Result<(), ContractError> {
+        let pending = read_pending_admin(&env)?;
+        pending.new_admin.require_auth();
+        if let Some(expires_at) = pending.expires_at {
+            if env.ledger().timestamp() > expires_at {
+                return Err(ContractError::AdminTransferExpired);
+            }
+        }
+        env.storage().instance().set(&DataKey::Admin, &pending.new_admin);
+        env.storage().instance().remove(&DataKey::PendingAdmin);
+        env.events()
+            .publish((Symbol::new(&env, "accept_admin"),), pending.new_admin);
+        Ok(())
+    }
+
+    pub fn cancel_admin_transfer(env: Env) -> Result<(), ContractError> {
+        require_admin(&env)?;
+        let pending = read_pending_admin(&env)?;
+        env.storage().instance().remove(&DataKey::PendingAdmin);
+        env.events().publish(
+            (Symbol::new(&env, "cancel_admin_transfer"),),
+            pending.new_admin,
+        );
+        Ok(())
+    }
+
+    pub fn pending_admin(env: Env) -> Option<PendingAdmin> {
+        env.storage().instance().get(&DataKey::PendingAdmin)
+    }
+
+    /// Pauses every operation.
+    pub fn pause(env: Env) -> Result<(), ContractError> {
+        require_admin(&env)?;
+        set_pause_flags(&env, PAUSE_ALL)?;
+        env.events().publish((Symbol::new(&env, "pause"),), ());
+        Ok(())
+    }
+
+    /// Clears every pause flag.
+    pub fn unpause(env: Env) -> Result<(), ContractError> {
+        require_admin(&env)?;
+        set_pause_flags(&env, 0)?;
+        env.events().publish((Symbol::new(&env, "unpause"),), ());
+        Ok(())
+    }
+
+    /// Returns `true` if any operation is paused.
+    pub fn is_paused(env: Env) -> bool {
+        pause_flags(&env) != 0
+    }
+
+    /// Pauses the operations in `flags` (a combination of the `PAUSE_*`
+    /// constants) and leaves the others unchanged.
+    pub fn pause_operations(env: Env, flags: u32) -> Result<(), ContractError> {
+        require_admin(&env)?;
+        set_pause_flags(&env, pause_flags(&env) | flags)
+    }
+
+    /// Resumes the operations in `flags` and leaves the others unchanged.
+    pub fn unpause_operations(env: Env, flags: u32) -> Result<(), ContractError> {
+        require_admin(&env)?;
+        if flags & !PAUSE_ALL != 0 {
+            return Err(ContractError::InvalidPauseFlags);
+        }
+        set_pause_flags(&env, pause_flags(&env) & !flags)
+    }
+
+    /// Returns the full pause state as a bitmask of `PAUSE_*` flags.
+    pub fn pause_flags(env: Env) -> u32 {
+        pause_flags(&env)
+    }
+
+    /// Sets the cap applied to users without a per-user cap. `None` removes it.
+    pub fn set_default_debit_cap(env: Env, cap: Option<DebitCap>) -> Result<(), ContractError> {
+        require_admin(&env)?;
+        validate_debit_cap(&cap)?;
+        match &cap {
+            Some(cap) => env.storage().instance().set(&DataKey::DefaultDebitCap, cap),
+            None => env.storage().instance().remove(&DataKey::DefaultDebitCap),
+        }
+        env.events()
+            .publish((Symbol::new(&env, "set_default_debit_cap"),), cap);
+        Ok(())
+    }
+
+    /// Sets a cap for `user` that overrides the default. `None` removes it, so
+    /// the default applies again.
+    pub fn set_debit_cap(
+        env: Env,
+        user: Address,
+        cap: Option<DebitCap>,
+    ) -> Result<(), ContractError> {
+        require_admin(&env)?;
+        validate_debit_cap(&cap)?;
+        let key = DataKey::DebitCap(user.clone());
+        match &cap {
+            Some(cap) => {
+                env.storage().persistent().set(&key, cap);
+                extend_persistent(&env, &key);
+            }
+            None => env.storage().persistent().remove(&key),
+        }
+        env.events()
+            .publish((Symbol::new(&env, "set_debit_cap"), user), cap);
+        Ok(())
+    }
+
+    /// Returns the cap that applies to `user`, if any.
+    pub fn debit_cap(env: Env, user: Address) -> Option<DebitCap> {
+        effective_debit_cap(&env, &user)
+    }
+
+    /// Returns how much of the cap `user` has used in the current period, or
+    /// `None` when no cap applies.
+    pub fn cap_usage(env: Env, user: Address) -> Option<CapUsage> {
+        effective_debit_cap(&env, &user)
+            .map(|cap| current_cap_usage(&env, &DataKey::CapUsage(user), &cap))
+    }
+
+    /// Sets the cap on debits of a non-default `asset` from `user`, or removes
+    /// it with `None`. Caps are per asset because amounts of different assets
+    /// are not comparable.
+    pub fn set_asset_debit_cap(
+        env: Env,
+        user: Address,
+        asset: Address,
+        cap: Option<DebitCap>,
+    ) -> Result<(), ContractError> {
+        require_admin(&env)?;
+        validate_debit_cap(&cap)?;
+        let key = DataKey::AssetDebitCap(user.clone(), asset.clone());
+        match &cap {
+            Some(cap) => {
+                env.storage().persistent().set(&key, cap);
+                extend_persistent(&env, &key);
+            }
+            None => env.storage().persistent().remove(&key),
+        }
+        env.events()
+            .publish((Symbol::new(&env, "set_asset_debit_cap"), user, asset), cap);
+        Ok(())
+    }
+
+    pub fn asset_debit_cap(env: Env, user: Address, asset: Address) -> Option<DebitCap> {
+        asset_debit_cap(&env, &user, &asset)
+    }
+
+    /// Returns how much of its cap on `asset` `user` has used in the current
+    /// period, or `None` when no cap is set for that asset.
+    pub fn asset_cap_usage(env: Env, user: Address, asset: Address) -> Option<CapUsage> {
+        asset_debit_cap(&env, &user, &asset).map(|cap| {
+            current_cap_usage(&env, &DataKey::AssetCapUsage(user, asset), &cap)
+        })
+    }
+
+    /// Turns on the outflow circuit breaker, or turns it off with `None`.
+    /// Changing the config starts a fresh window.
+    pub fn set_breaker_config(env: Env, config: Option<BreakerConfig>) -> Result<(), ContractError> {
+        require_admin(&env)?;
+        match &config {
+            Some(config) => {
+                if config.threshold <= 0 || config.window_secs < BREAKER_SLOTS {
+                    return Err(ContractError::InvalidBreakerConfig);
+                }
+                env.storage().instance().set(&DataKey::BreakerConfig, config);
+            }
+            None => env.storage().instance().remove(&DataKey::BreakerConfig),
+        }
+        env.storage().instance().remove(&DataKey::OutflowSlots);
+        env.events()
+            .publish((Symbol::new(&env, "breaker_config"),), config);
+        Ok(())
+    }
+
+    pub fn breaker_config(env: Env) -> Option<BreakerConfig> {
+        breaker_config(&env)
+    }
+
+    /// Amount that has left the wallet in the current breaker window. Always
+    /// 0 while the breaker is off.
+    pub fn window_outflow(env: Env) -> i128 {
+        window_outflow(&env)
+    }
+
+    /// Sends `debit` and `withdraw` calls above `config.threshold` through the
+    /// withdrawal queue, or turns the queue off with `None`. Items already
+    /// queued are unaffected.
+    pub fn set_queue_config(env: Env, config: Option<QueueConfig>) -> Result<(), ContractError> {
+        require_admin(&env)?;
+        match &config {
+            Some(config) => {
+                if config.threshold <= 0 || config.delay_secs == 0 {
+                    return Err(ContractError::InvalidQueueConfig);
+                }
+                env.storage().instance().set(&DataKey::QueueConfig, config);
+            }
+            None => env.storage().instance().remove(&DataKey::QueueConfig),
+        }
+        env.events()
+            .publish((Symbol::new(&env, "queue_config"),), config);
+        Ok(())
+    }
+
+    pub fn queue_config(env: Env) -> Option<QueueConfig> {
+        env.storage().instance().get(&DataKey::QueueConfig)
+    }
+
+    /// Runs a queued outflow once its delay has passed. Anyone may call
+    /// this. Returns `OutflowStatus::Blocked` and keeps the item queued if it
+    /// trips the circuit breaker.
+    pub fn execute_withdrawal(env: Env, id: u64) -> Result<OutflowStatus, ContractError> {
+        let queued = read_withdrawal(&env, id)?;
+        let flag = match queued.outflow {
+            Outflow::Withdraw(_) => PAUSE_WITHDRAW,
+            _ => PAUSE_DEBIT,
+        };
+        require_not_paused(&env, flag)?;
+        if env.ledger().timestamp() < queued.execute_after {
+            return Err(ContractError::WithdrawalNotReady);
+        }
+        // A tripped breaker leaves the item queued for after the review.
+        if trip_breaker(&env, queued.amount, &queued.outflow)? {
+            return Ok(OutflowStatus::Blocked);
+        }
+
+        dequeue_withdrawal(&env, id, &queued);
+        run_outflow(&env, queued.user, queued.amount, queued.outflow)?;
+        env.events()
+            .publish((Symbol::new(&env, "withdrawal_executed"), id), queued.amount);
+        Ok(OutflowStatus::Executed)
+    }
+
+    /// Cancels a queued withdrawal before it executes and releases its
+    /// reservation. Signed by the guardian recorded on the item. A cancelled
+    /// `debit_bucket` goes back into its bucket. A cancelled `capture` leaves
+    /// the funds available: its hold was released when it was queued.
+    pub fn cancel_withdrawal(env: Env, id: u64) -> Result<(), ContractError> {
+        let queued = read_withdrawal(&env, id)?;
+        queued.guardian.require_auth();
+        dequeue_withdrawal(&env, id, &queued);
+        if let Outflow::Bucket(bucket) = &queued.outflow {
+            adjust_bucket(&env, &queued.user, bucket, queued.amount)?;
+        }
+
+        env.events()
+            .publish((Symbol::new(&env, "withdrawal_cancelled"), id), queued.amount);
+        Ok(())
+    }
+
+    pub fn get_withdrawal(env: Env, id: u64) -> Option<QueuedWithdrawal> {
+        env.storage().persistent().get(&DataKey::Withdrawal(id))
+    }
+
+    /// Sets the platform fee charged on `debit` and `debit_with_ref`, or
+    /// removes it with `None`.
+    pub fn set_fee_config(env: Env, config: Option<FeeConfig>) -> Result<(), ContractError> {
+        require_admin(&env)?;
+        match &config {
+            Some(config) => {
+                if config.bps > 10_000 || config.min_fee < 0 {
+                    return Err(ContractError::InvalidFeeConfig);
+                }
+                env.storage().instance().set(&DataKey::FeeConfig, config);
+            }
+            None => env.storage().instance().remove(&DataKey::FeeConfig),
+        }
+        env.events()
+            .publish((Symbol::new(&env, "fee_config"),), config);
+        Ok(())
+    }
+
+    pub fn fee_config(env: Env) -> Option<FeeConfig> {
+        fee_config(&env)
+    }
+
+    /// Fee that a debit of `amount` would pay under the current schedule.
+    pub fn quote_fee(env: Env, amount: i128) -> Result<i128, ContractError> {
+        require_positive(amount)?;
+        match fee_config(&env) {
+            Some(config) => compute_fee(&config, amount),
+            None => Ok(0),
+        }
+    }
+
+    /// Lets the balance of `user` go negative down to `-limit`. A limit of 0
+    /// removes the credit line. Lowering the limit below the credit already
+    /// used blocks further debits until the user repays.
+    pub fn set_credit_limit(env: Env, user: Address, limit: i128) -> Result<(), ContractError> {
+        require_admin(&env)?;
+        if limit < 0 {
+            return Err(ContractError::InvalidAmount);
+        }
+        let old = credit_limit(&env, &user);
+        let key = DataKey::CreditLimit(user.clone());
+        if limit == 0 {
+            env.storage().persistent().remove(&key);
+        } else {
+            env.storage().persistent().set(&key, &limit);
+            extend_persistent(&env, &key);
+        }
+        env.events()
+            .publish((Symbol::new(&env, "credit_limit"), user), (old, limit));
+        Ok(())
+    }
+
+    pub fn credit_limit(env: Env, user: Address) -> i128 {
+        credit_limit(&env, &user)
+    }
+
+    /// Credit currently drawn by `user`, i.e. how far the balance is below
+    /// zero.
+    pub fn credit_used(env: Env, user: Address) -> i128 {
+        (-read_balance(&env, &user)).max(0)
+    }
+
+    /// Credit `user` can still draw, after funds reserved by holds.
+    pub fn credit_available(env: Env, user: Address) -> i128 {
+        spendable_balance(&env, &user)
+            .min(credit_limit(&env, &user))
+            .max(0)
+    }
+
+    /// Freezes a single account. Credits, debits, holds and transfers
+    /// touching it are rejected until `unfreeze`. `reason` is an
+    /// application-defined compliance code.
+    pub fn freeze(env: Env, user: Address, reason: u32) -> Result<(), ContractError> {
+        require_admin(&env)?;
+        let key = DataKey::Frozen(user.clone());
+        env.storage().persistent().set(&key, &reason);
+        extend_persistent(&env, &key);
+        env.events()
+            .publish((Symbol::new(&env, "freeze"), user), reason);
+        Ok(())
+    }
+
+    /// Lifts a freeze. The event carries the reason code the account was
+    /// frozen with.
+    pub fn unfreeze(env: Env, user: Address) -> Result<(), ContractError> {
+        require_admin(&env)?;
+        let reason = frozen_reason(&env, &user).ok_or(ContractError::AccountNotFrozen)?;
+        env.storage()
+            .persistent()
+            .remove(&DataKey::Frozen(user.clone()));
+        env.events()
+            .publish((Symbol::new(&env, "unfreeze"), user), reason);
+        Ok(())
+    }
+
+    pub fn is_frozen(env: Env, user: Address) -> bool {
+        frozen_reason(&env, &user).is_some()
+    }
+
+    /// Returns the reason code of an active freeze.
+    pub fn frozen_reason(env: Env, user: Address) -> Option<u32> {
+        frozen_reason(&env, &user)
+    }
+
+    pub fn add_operator(env: Env, operator: Address) -> Result<(), ContractError> {
+        require_admin(&env)?;
+        let mut ops = operators(&env);
+        ops.set(operator.clone(), true);
+        env.storage().instance().set(&DataKey::Operators, &ops);
+        env.events()
+            .publish((Symbol::new(&env, "add_operator"),), operator);
+        Ok(())
+    }
+
+    pub fn remove_operator(env: Env, operator: Address) -> Result<(), ContractError> {
+        require_admin(&env)?;
+        let mut ops = operators(&env);
+        ops.remove(operator.clone());
+        env.storage().instance().set(&DataKey::Operators, &ops);
+        env.events()
+            .publish((Symbol::new(&env, "remove_operator"),), operator);
+        Ok(())
+    }
+
+    pub fn is_operator(env: Env, operator: Address) -> bool {
+        operators(&env).get(operator).unwrap_or(false)
+    }
+
+    /// Moves `amount` from the balance of `from` to the balance of `to`.
+    /// Signed by `from`; the admin is not involved.
+    pub fn transfer(env: Env, from: Address, to: Address, amount: i128) -> Result<(), ContractError> {
+        from.require_auth();
+        require_not_paused(&env, PAUSE_TRANSFER)?;
+        require_positive(amount)?;
+        if from == to {
+            return Err(ContractError::SelfTransfer);
+        }
+
+        let from_balance = debit_balance(&env, &from, amount, "transfer_out", None, false)?;
+        let to_balance = credit_balance(&env, &to, amount, "transfer_in", None)?;
+
+        env.events().publish(
+            (Symbol::new(&env, "transfer"), from, to),
+            (amount, from_balance, to_balance),
+        );
+        Ok(())
+    }
+
+    /// Lets `spender` debit up to `amount` from the balance of `owner` until
+    /// `expiration_ledger`. Replaces any existing allowance; an amount of 0
+    /// revokes it.
+    pub fn approve(
+        env: Env,
+        owner: Address,
+        spender: Address,
+        amount: i128,
+        expiration_ledger: u32,
+    ) -> Result<(), ContractError> {
+        owner.require_auth();
+        if amount < 0 {
+            return Err(ContractError::InvalidAmount);
+        }
+        write_allowance(&env, &owner, &spender, amount, expiration_ledger)?;
+
+        env.events().publish(
+            (Symbol::new(&env, "approve"), owner, spender),
+            (amount, expiration_ledger),
+        );
+        Ok(())
+    }
+
+    pub fn allowance(env: Env, owner: Address, spender: Address) -> i128 {
+        read_allowance(&env, &owner, &spender)
+    }
+
+    /// Moves `amount` from the balance of `owner` to the balance of
+    /// `spender` against the allowance granted to `spender`. Signed by
+    /// `spender`; the admin is not involved. The money stays in the wallet,
+    /// so like `transfer` it is not charged a fee or counted as an outflow.
+    pub fn debit_from(
+        env: Env,
+        spender: Address,
+        owner: Address,
+        amount: i128,
+    ) -> Result<(), ContractError> {
+        spender.require_auth();
+        require_not_paused(&env, PAUSE_DEBIT | PAUSE_TRANSFER)?;
+        require_positive(amount)?;
+        if spender == owner {
+            return Err(ContractError::SelfTransfer);
+        }
+
+        let allowance = read_allowance(&env, &owner, &spender);
+        if allowance < amount {
+            return Err(ContractError::InsufficientAllowance);
+        }
+        let owner_balance = debit_balance(&env, &owner, amount, "debit_from", None, false)?;
+        let spender_balance = credit_balance(&env, &spender, amount, "debit_from_in", None)?;
+        let key = DataKey::Allowance(owner.clone(), spender.clone());
+        let mut remaining: Allowance = env.storage().temporary().get(&key).unwrap();
+        remaining.amount = allowance - amount;
+        env.storage().temporary().set(&key, &remaining);
+
+        env.events().publish(
+            (Symbol::new(&env, "debit_from"), spender, owner),
+            (amount, owner_balance, spender_balance),
+        );
+        Ok(())
+    }
+
+    /// Schedules `count` payments of `amount` from `user` to `beneficiary`,
+    /// the first due at `start` and the rest every `interval_secs` after it.
+    /// Returns the order id.
+    pub fn create_standing_order(
+        env: Env,
+        user: Address,
+        beneficiary: Address,
+        amount: i128,
+        interval_secs: u64,
+        start: u64,
+        count: u32,
+    ) -> Result<u64, ContractError> {
+        user.require_auth();
+        require_not_frozen(&env, &user)?;
+        require_positive(amount)?;
+        if user == beneficiary {
+            return Err(ContractError::SelfTransfer);
+        }
+        if interval_secs == 0 || count == 0 {
+            return Err(ContractError::InvalidSchedule);
+        }
+
+        let order_id: u64 = env
+            .storage()
+            .instance()
+            .get(&DataKey::StandingOrderCount)
+            .unwrap_or(0);
+        let order = StandingOrder {
+            user: user.clone(),
+            beneficiary: beneficiary.clone(),
+            amount,
+            interval_secs,
+            next_due: start,
+            remaining: count,
+            executed: 0,
+            missed: 0,
+        };
+        write_standing_order(&env, order_id, &order);
+        env.storage()
+            .instance()
+            .set(&DataKey::StandingOrderCount, &(order_id + 1));
+
+        env.events().publish(
+            (Symbol::new(&env, "order_created"), user, beneficiary),
+            (order_id, amount, interval_secs, start, count),
+        );
+        Ok(order_id)
+    }
+
+    /// Runs the next due payment of a standing order. Anyone may call this.
+    /// Returns `true` if the payment went through, or `false` if it was
+    /// recorded as missed because the user's available balance was too low.
+    /// Either way the order moves on to its next due date.
+    pub fn execute_due(env: Env, order_id: u64) -> Result<bool, ContractError> {
+        require_not_paused(&env, PAUSE_TRANSFER)?;
+        let mut order = read_standing_order(&env, order_id)?;
+        let due_at = order.next_due;
+        if env.ledger().timestamp() < due_at {
+            return Err(ContractError::StandingOrderNotDue);
+        }
+
+        order.next_due = due_at.saturating_add(order.interval_secs);
+        order.remaining -= 1;
+        let paid = match debit_balance(
+            &env,
+            &order.user,
+            order.amount,
+            "order_out",
+            None,
+            false,
+        ) {
+            Ok(user_balance) => {
+                let beneficiary_balance = credit_balance(
+                    &env,
+                    &order.beneficiary,
+                    order.amount,
+                    "order_in",
+                    None,
+                )?;
+                order.executed += 1;
+                env.events().publish(
+                    (Symbol::new(&env, "order_executed"), order_id),
+                    (order.amount, user_balance, beneficiary_balance),
+                );
+                true
+            }
+            Err(ContractError::InsufficientBalance) => {
+                order.missed += 1;
+                env.events().publish(
+                    (Symbol::new(&env, "order_missed"), order_id),
+                    (order.amount, due_at),
+                );
+                false
+            }
+            Err(e) => return Err(e),
+        };
+        write_standing_order(&env, order_id, &order);
+        Ok(paid)
+    }
+
+    /// Cancels the remaining runs of a standing order. Signed by its user.
+    pub fn cancel_standing_order(env: Env, order_id: u64) -> Result<(), ContractError> {
+        let order = read_standing_order(&env, order_id)?;
+        order.user.require_auth();
+        env.storage()
+            .persistent()
+            .remove(&DataKey::StandingOrder(order_id));
+
+        env.events()
+            .publish((Symbol::new(&env, "order_cancelled"), order_id), order.remaining);
+        Ok(())
+    }
+
+    pub fn get_standing_order(env: Env, order_id: u64) -> Option<StandingOrder> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::StandingOrder(order_id))
+    }
+
+    /// Configures the SEP-41 token (e.g. USDC) that backs wallet balances.
+    /// Once set, the token can only change while no user has a positive
+    /// balance.
+    pub fn set_token(env: Env, token: Address) -> Result<(), ContractError> {
+        require_admin(&env)?;
+        let current: Option<Address> = env.storage().instance().get(&DataKey::Token);
+        if current.is_some_and(|current| current != token) && owed(&env) != 0 {
+            return Err(ContractError::TokenInUse);
+        }
+        env.storage().instance().set(&DataKey::Token, &token);
+        env.events().publish((Symbol::new(&env, "set_token"),), token);
+        Ok(())
+    }
+
+    pub fn token(env: Env) -> Option<Address> {
+        env.storage().instance().get(&DataKey::Token)
+    }
+
+    /// Pulls `amount` of `token` from `user` into the contract and credits the
+    /// same amount to the user's wallet balance.
+    pub fn deposit(
+        env: Env,
+        user: Address,
+        token: Address,
+        amount: i128,
+    ) -> Result<(), ContractError> {
+        user.require_auth();
+        require_not_paused(&env, PAUSE_CREDIT)?;
+        require_positive(amount)?;
+        if token != get_token(&env)? {
+            return Err(ContractError::UnsupportedToken);
+        }
+
+        token::Client::new(&env, &token).transfer(
+            &user,
+            &env.current_contract_address(),
+            &amount,
+        );
+        let new_balance = apply_credit(&env, &user, amount, "deposit", None)?;
+
+        env.events()
+            .publish((Symbol::new(&env, "deposit"), user), (amount, new_balance));
+        Ok(())
+    }
+
+    /// Debits `amount` from the wallet balance of `user` and pays the same
+    /// amount of the configured token out to `to`. A large withdrawal returns
+    /// `OutflowStatus::Queued` with the id to execute or cancel it by.
+    pub fn withdraw(
+        env: Env,
+        user: Address,
+        amount: i128,
+        to: Address,
+    ) -> Result<OutflowStatus, ContractError> {
+        user.require_auth();
+        require_not_paused(&env, PAUSE_WITHDRAW)?;
+        require_positive(amount)?;
+        get_token(&env)?;
+
+        start_outflow(&env, user, amount, Outflow::Withdraw(to))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    extern crate std;
+
+
+
+    use super::{
+        AccountPage, BalanceDetails, BreakerConfig, CapUsage, ContractError, DataKey, DebitCap,
+        FeeConfig, Hold, JournalEntry, Outflow, OutflowStatus, PendingAdmin, QueueConfig,
+        QueuedWithdrawal,
+        RentWallet,
+        RentWalletClient, StandingOrder, Totals, TtlConfig, BALANCE_BUMP_AMOUNT,
+        BALANCE_LIFETIME_THRESHOLD, BREAKER_SLOTS, MAIN_BUCKET, PAUSE_ALL, PAUSE_CREDIT,
+        PAUSE_DEBIT, PAUSE_TRANSFER, PAUSE_WITHDRAW, SCHEMA_VERSION,
+    };
+    use soroban_sdk::testutils::storage::{Instance as _, Persistent as _};
+    use soroban_sdk::testutils::{Address as _, Events, Ledger, MockAuth, MockAuthInvoke};
+    use soroban_sdk::token::{StellarAssetClient, TokenClient};
+    use soroban_sdk::{Address, BytesN, Env, IntoVal, Map, Symbol, TryIntoVal};
 
 
     fn setup(env: &Env) -> (soroban_sdk::Address, RentWalletClient<'_>, Address, Address, Address) {
 
-        let contract_id = env.register_contract(None, RentWallet);
+        let contract_id = env.register(RentWallet, ());
+
+        let client = RentWalletClient::new(env, &contract_id);
+
+        let admin = Address::generate(env);
+
+        let user = Address::generate(env);
+
+        let non_admin = Address::generate(env);
+
+        client.init(&admin);
+
+        (contract_id, client, admin, user, non_admin)
+
+    }
+
+    fn setup_token<'a>(env: &Env, client: &RentWalletClient<'_>) -> (Address, TokenClient<'a>, StellarAssetClient<'a>) {
+        let issuer = Address::generate(env);
+        let sac = env.register_stellar_asset_contract_v2(issuer);
+        let token = sac.address();
+        client.set_token(&token);
+        (token.clone(), TokenClient::new(env, &token), StellarAssetClient::new(env, &token))
+    }
+
+
+    // ============================================================================
+    // Init Tests
+    // ============================================================================
+
+    #[test]
+    fn init_sets_admin() {
+        let env = Env::default();
+        let contract_id = env.register(RentWallet, ());
+        let client = RentWalletClient::new(&env, &contract_id);
+        let admin = Address::generate(&env);
+
+        client.init(&admin);
+
+        // Admin should be able to perform admin operations
+        let user = Address::generate(&env);
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "credit",
+                args: (user.clone(), 100i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.credit(&user, &100i128);
+        assert_eq!(client.balance(&user), 100i128);
+    }
+
+    #[test]
+    fn init_initializes_empty_balances() {
+        let env = Env::default();
+        let contract_id = env.register(RentWallet, ());
+        let client = RentWalletClient::new(&env, &contract_id);
+        let admin = Address::generate(&env);
+        let user = Address::generate(&env);
+
+        client.init(&admin);
+
+        // Balance should be zero for any user initially
+        assert_eq!(client.balance(&user), 0i128);
+    }
+
+    #[test]
+    fn init_cannot_be_called_twice() {
+        let env = Env::default();
+        let contract_id = env.register(RentWallet, ());
+        let client = RentWalletClient::new(&env, &contract_id);
+        let admin = Address::generate(&env);
+
+        client.init(&admin);
+        assert_eq!(
+            client.try_init(&admin),
+            Err(Ok(ContractError::AlreadyInitialized))
+        );
+    }
+
+    // ============================================================================
+    // Credit Tests
+    // ============================================================================
+
+    #[test]
+    fn credit_increases_balance() {
+        let env = Env::default();
+        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "credit",
+                args: (user.clone(), 100i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+
+        assert_eq!(client.balance(&user), 0i128);
+        client.credit(&user, &100i128);
+        assert_eq!(client.balance(&user), 100i128);
+    }
+
+    #[test]
+    fn credit_accumulates_balance() {
+        let env = Env::default();
+        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "credit",
+                args: (user.clone(), 50i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.credit(&user, &50i128);
+        assert_eq!(client.balance(&user), 50i128);
+
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "credit",
+                args: (user.clone(), 75i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.credit(&user, &75i128);
+        assert_eq!(client.balance(&user), 125i128);
+    }
+
+    #[test]
+    fn credit_fails_with_zero_amount() {
+        let env = Env::default();
+        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "credit",
+                args: (user.clone(), 0i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+
+        assert_eq!(
+            client.try_credit(&user, &0i128),
+            Err(Ok(ContractError::InvalidAmount))
+        );
+    }
+
+    #[test]
+    fn credit_fails_with_negative_amount() {
+        let env = Env::default();
+        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "credit",
+                args: (user.clone(), -10i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+
+        assert_eq!(
+            client.try_credit(&user, &-10i128),
+            Err(Ok(ContractError::InvalidAmount))
+        );
+    }
+
+    // ============================================================================
+    // Debit Tests
+    // ============================================================================
+
+    #[test]
+    fn debit_decreases_balance() {
+        let env = Env::default();
+        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+
+        // First credit some balance
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "credit",
+                args: (user.clone(), 100i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.credit(&user, &100i128);
+        assert_eq!(client.balance(&user), 100i128);
+
+        // Then debit
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "debit",
+                args: (user.clone(), 30i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.debit(&user, &30i128);
+        assert_eq!(client.balance(&user), 70i128);
+    }
+
+    #[test]
+    fn debit_can_reduce_balance_to_zero() {
+        let env = Env::default();
+        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+
+        // Credit balance
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "credit",
+                args: (user.clone(), 50i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.credit(&user, &50i128);
+
+        // Debit entire balance
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "debit",
+                args: (user.clone(), 50i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.debit(&user, &50i128);
+        assert_eq!(client.balance(&user), 0i128);
+    }
+
+    #[test]
+    fn debit_fails_with_insufficient_balance() {
+        let env = Env::default();
+        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+
+        // Credit some balance
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "credit",
+                args: (user.clone(), 50i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.credit(&user, &50i128);
+
+        // Try to debit more than available
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "debit",
+                args: (user.clone(), 100i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+
+        assert_eq!(
+            client.try_debit(&user, &100i128),
+            Err(Ok(ContractError::InsufficientBalance))
+        );
+    }
+
+    #[test]
+    fn debit_fails_when_balance_is_zero() {
+        let env = Env::default();
+        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "debit",
+                args: (user.clone(), 1i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+
+        assert_eq!(
+            client.try_debit(&user, &1i128),
+            Err(Ok(ContractError::InsufficientBalance))
+        );
+    }
+
+    #[test]
+    fn debit_fails_with_zero_amount() {
+        let env = Env::default();
+        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+
+        // First credit some balance
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "credit",
+                args: (user.clone(), 100i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.credit(&user, &100i128);
+
+        // Try to debit zero
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "debit",
+                args: (user.clone(), 0i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+
+        assert_eq!(
+            client.try_debit(&user, &0i128),
+            Err(Ok(ContractError::InvalidAmount))
+        );
+    }
+
+    #[test]
+    fn debit_fails_with_negative_amount() {
+        let env = Env::default();
+        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+
+        // First credit some balance
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "credit",
+                args: (user.clone(), 100i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.credit(&user, &100i128);
+
+        // Try to debit negative amount
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "debit",
+                args: (user.clone(), -10i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+
+        assert_eq!(
+            client.try_debit(&user, &-10i128),
+            Err(Ok(ContractError::InvalidAmount))
+        );
+    }
+
+    // ============================================================================
+    // Balance Tests
+    // ============================================================================
+
+    #[test]
+    fn balance_returns_zero_for_new_user() {
+        let env = Env::default();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let new_user = Address::generate(&env);
+
+        assert_eq!(client.balance(&user), 0i128);
+        assert_eq!(client.balance(&new_user), 0i128);
+    }
+
+    #[test]
+    fn balance_reflects_credit_and_debit_operations() {
+        let env = Env::default();
+        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+
+        // Initial balance
+        assert_eq!(client.balance(&user), 0i128);
+
+        // After credit
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "credit",
+                args: (user.clone(), 200i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.credit(&user, &200i128);
+        assert_eq!(client.balance(&user), 200i128);
+
+        // After debit
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "debit",
+                args: (user.clone(), 80i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.debit(&user, &80i128);
+        assert_eq!(client.balance(&user), 120i128);
+    }
+
+    // ============================================================================
+    // Admin Authorization Tests
+    // ============================================================================
+
+
+    #[test]
+    #[should_panic]
+    fn non_admin_cannot_credit() {
+
+        let env = Env::default();
+
+        let (contract_id, client, _admin, user, non_admin) = setup(&env);
+
+
+
+        env.mock_auths(&[MockAuth {
+
+            address: &non_admin,
+
+            invoke: &MockAuthInvoke {
+
+                contract: &contract_id,
+
+                fn_name: "credit",
+
+                args: (user.clone(), 100i128).into_val(&env),
+
+                sub_invokes: &[],
+
+            },
+
+        }]);
+
+
+
+        client.credit(&user, &100i128);
+
+    }
+
+
+
+    #[test]
+    #[should_panic]
+    fn non_admin_cannot_debit() {
+
+        let env = Env::default();
+
+        let (contract_id, client, _admin, user, non_admin) = setup(&env);
+
+
+
+        env.mock_auths(&[MockAuth {
+
+            address: &non_admin,
+
+            invoke: &MockAuthInvoke {
+
+                contract: &contract_id,
+
+                fn_name: "debit",
+
+                args: (user.clone(), 1i128).into_val(&env),
+
+                sub_invokes: &[],
+
+            },
+
+        }]);
+
+
+
+        client.debit(&user, &1i128);
+
+    }
+
+
+    #[test]
+    fn admin_handover_requires_acceptance() {
+        let env = Env::default();
+        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+        let new_admin = Address::generate(&env);
+
+        // Original admin proposes the new admin
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "propose_admin",
+                args: (new_admin.clone(), None::<u64>).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.propose_admin(&new_admin, &None);
+        assert_eq!(
+            client.pending_admin(),
+            Some(PendingAdmin {
+                new_admin: new_admin.clone(),
+                expires_at: None,
+            })
+        );
+
+        // New admin accepts
+        env.mock_auths(&[MockAuth {
+            address: &new_admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "accept_admin",
+                args: ().into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.accept_admin();
+        assert_eq!(client.pending_admin(), None);
+
+        // New admin should be able to perform admin operations
+        env.mock_auths(&[MockAuth {
+            address: &new_admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "credit",
+                args: (user.clone(), 50i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.credit(&user, &50i128);
+        assert_eq!(client.balance(&user), 50i128);
+    }
+
+    #[test]
+    #[should_panic]
+    fn old_admin_loses_access_after_handover() {
+        let env = Env::default();
+        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+        let new_admin = Address::generate(&env);
+        env.mock_all_auths();
+        client.propose_admin(&new_admin, &None);
+        client.accept_admin();
+
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "credit",
+                args: (user.clone(), 50i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.credit(&user, &50i128);
+    }
+
+    #[test]
+    #[should_panic]
+    fn non_admin_cannot_propose_admin() {
+        let env = Env::default();
+        let (contract_id, client, _admin, _user, non_admin) = setup(&env);
+        let new_admin = Address::generate(&env);
+
+        env.mock_auths(&[MockAuth {
+            address: &non_admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "propose_admin",
+                args: (new_admin.clone(), None::<u64>).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.propose_admin(&new_admin, &None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn accept_admin_requires_proposed_admin_auth() {
+        let env = Env::default();
+        let (contract_id, client, _admin, _user, non_admin) = setup(&env);
+        let new_admin = Address::generate(&env);
+        env.mock_all_auths();
+        client.propose_admin(&new_admin, &None);
+
+        env.mock_auths(&[MockAuth {
+            address: &non_admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "accept_admin",
+                args: ().into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.accept_admin();
+    }
+
+    #[test]
+    fn accept_admin_fails_after_expiry() {
+        let env = Env::default();
+        env.mock_all_auths();
+        env.ledger().set_timestamp(1_000);
+        let (_contract_id, client, _admin, _user, _non_admin) = setup(&env);
+        let new_admin = Address::generate(&env);
+        client.propose_admin(&new_admin, &Some(2_000u64));
+
+        env.ledger().set_timestamp(2_001);
+        assert_eq!(
+            client.try_accept_admin(),
+            Err(Ok(ContractError::AdminTransferExpired))
+        );
+    }
+
+    #[test]
+    fn cancel_admin_transfer_clears_proposal() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, _user, _non_admin) = setup(&env);
+        let new_admin = Address::generate(&env);
+        client.propose_admin(&new_admin, &None);
+
+        client.cancel_admin_transfer();
+        assert_eq!(client.pending_admin(), None);
+        assert_eq!(
+            client.try_accept_admin(),
+            Err(Ok(ContractError::NoPendingAdmin))
+        );
+    }
+
+    #[test]
+    fn admin_handover_emits_events() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, _user, _non_admin) = setup(&env);
+        let new_admin = Address::generate(&env);
+
+        client.propose_admin(&new_admin, &Some(500u64));
+        let event = env.events().all().last().unwrap();
+        let event_name: Symbol = event.1.get(0).unwrap().try_into_val(&env).unwrap();
+        assert_eq!(event_name, Symbol::new(&env, "propose_admin"));
+        let data: (Address, Option<u64>) = event.2.try_into_val(&env).unwrap();
+        assert_eq!(data, (new_admin.clone(), Some(500u64)));
+
+        client.cancel_admin_transfer();
+        let event = env.events().all().last().unwrap();
+        let event_name: Symbol = event.1.get(0).unwrap().try_into_val(&env).unwrap();
+        assert_eq!(event_name, Symbol::new(&env, "cancel_admin_transfer"));
+
+        client.propose_admin(&new_admin, &None);
+        client.accept_admin();
+        let event = env.events().all().last().unwrap();
+        let event_name: Symbol = event.1.get(0).unwrap().try_into_val(&env).unwrap();
+        assert_eq!(event_name, Symbol::new(&env, "accept_admin"));
+        let data: Address = event.2.try_into_val(&env).unwrap();
+        assert_eq!(data, new_admin);
+    }
+
+
+    #[test]
+    fn admin_can_pause() {
+        let env = Env::default();
+        let (contract_id, client, admin, _user, _non_admin) = setup(&env);
+
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "pause",
+                args: ().into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+
+        client.pause();
+        assert!(client.is_paused());
+    }
+
+    #[test]
+    fn admin_can_unpause() {
+        let env = Env::default();
+        let (contract_id, client, admin, _user, _non_admin) = setup(&env);
+
+        // First pause
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "pause",
+                args: ().into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.pause();
+        assert!(client.is_paused());
+
+        // Then unpause
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "unpause",
+                args: ().into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.unpause();
+        assert!(!client.is_paused());
+    }
+
+    #[test]
+    #[should_panic]
+    fn non_admin_cannot_pause() {
+        let env = Env::default();
+        let (contract_id, client, _admin, _user, non_admin) = setup(&env);
+
+        env.mock_auths(&[MockAuth {
+            address: &non_admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "pause",
+                args: ().into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+
+        client.pause();
+    }
+
+    #[test]
+    #[should_panic]
+    fn non_admin_cannot_unpause() {
+        let env = Env::default();
+        let (contract_id, client, admin, _user, non_admin) = setup(&env);
+
+        // First pause as admin
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "pause",
+                args: ().into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.pause();
+
+        // Try to unpause as non-admin
+        env.mock_auths(&[MockAuth {
+            address: &non_admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "unpause",
+                args: ().into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+
+        client.unpause();
+    }
+
+    #[test]
+    fn credit_fails_when_paused() {
+        let env = Env::default();
+        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+
+        // Pause the contract
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "pause",
+                args: ().into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.pause();
+
+        // Try to credit while paused
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "credit",
+                args: (user.clone(), 100i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+
+        assert_eq!(
+            client.try_credit(&user, &100i128),
+            Err(Ok(ContractError::ContractPaused))
+        );
+    }
+
+    #[test]
+    fn debit_fails_when_paused() {
+        let env = Env::default();
+        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+
+        // First credit some balance
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "credit",
+                args: (user.clone(), 100i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.credit(&user, &100i128);
+
+        // Pause the contract
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "pause",
+                args: ().into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.pause();
+
+        // Try to debit while paused
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "debit",
+                args: (user.clone(), 50i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+
+        assert_eq!(
+            client.try_debit(&user, &50i128),
+            Err(Ok(ContractError::ContractPaused))
+        );
+    }
+
+    #[test]
+    fn balance_works_when_paused() {
+        let env = Env::default();
+        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+
+        // Credit some balance
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "credit",
+                args: (user.clone(), 100i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.credit(&user, &100i128);
+        assert_eq!(client.balance(&user), 100i128);
+
+        // Pause the contract
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "pause",
+                args: ().into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.pause();
+
+        // Balance should still be readable
+        assert_eq!(client.balance(&user), 100i128);
+    }
+
+    #[test]
+    fn is_paused_returns_false_initially() {
+        let env = Env::default();
+        let (_contract_id, client, _admin, _user, _non_admin) = setup(&env);
+        assert!(!client.is_paused());
+    }
+
+    // ============================================================================
+    // Event Tests
+    // ============================================================================
+
+    #[test]
+    fn credit_emits_event() {
+        let env = Env::default();
+        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "credit",
+                args: (user.clone(), 100i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+
+        client.credit(&user, &100i128);
+
+        let events = env.events().all();
+        let event = events.last().unwrap();
+
+        let topics: soroban_sdk::Vec<soroban_sdk::Val> = event.1.clone();
+        assert_eq!(topics.len(), 2);
+        
+        let event_name: Symbol = topics.get(0).unwrap().try_into_val(&env).unwrap();
+        assert_eq!(event_name, Symbol::new(&env, "credit"));
+        
+        let event_user: Address = topics.get(1).unwrap().try_into_val(&env).unwrap();
+        assert_eq!(event_user, user);
+
+        let data: i128 = event.2.try_into_val(&env).unwrap();
+        assert_eq!(data, 100i128);
+    }
+
+    #[test]
+    fn debit_emits_event() {
+        let env = Env::default();
+        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "credit",
+                args: (user.clone(), 200i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.credit(&user, &200i128);
+
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "debit",
+                args: (user.clone(), 50i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.debit(&user, &50i128);
+
+        let events = env.events().all();
+        let event = events.last().unwrap();
+
+        let topics: soroban_sdk::Vec<soroban_sdk::Val> = event.1.clone();
+        assert_eq!(topics.len(), 2);
+        
+        let event_name: Symbol = topics.get(0).unwrap().try_into_val(&env).unwrap();
+        assert_eq!(event_name, Symbol::new(&env, "debit"));
+        
+        let event_user: Address = topics.get(1).unwrap().try_into_val(&env).unwrap();
+        assert_eq!(event_user, user);
+
+        let data: (i128, i128, i128, i128) = event.2.try_into_val(&env).unwrap();
+        assert_eq!(data, (50i128, 0i128, 50i128, 150i128));
+    }
+
+    // ============================================================================
+    // Operator Tests
+    // ============================================================================
+
+    fn add_operator(env: &Env, contract_id: &Address, client: &RentWalletClient<'_>, admin: &Address) -> Address {
+        let operator = Address::generate(env);
+        env.mock_auths(&[MockAuth {
+            address: admin,
+            invoke: &MockAuthInvoke {
+                contract: contract_id,
+                fn_name: "add_operator",
+                args: (operator.clone(),).into_val(env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.add_operator(&operator);
+        operator
+    }
+
+    #[test]
+    fn admin_can_add_operator() {
+        let env = Env::default();
+        let (contract_id, client, admin, _user, _non_admin) = setup(&env);
+
+        let operator = add_operator(&env, &contract_id, &client, &admin);
+        assert!(client.is_operator(&operator));
+    }
+
+    #[test]
+    fn admin_can_remove_operator() {
+        let env = Env::default();
+        let (contract_id, client, admin, _user, _non_admin) = setup(&env);
+        let operator = add_operator(&env, &contract_id, &client, &admin);
+
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "remove_operator",
+                args: (operator.clone(),).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.remove_operator(&operator);
+        assert!(!client.is_operator(&operator));
+    }
+
+    #[test]
+    fn is_operator_returns_false_for_non_operator() {
+        let env = Env::default();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        assert!(!client.is_operator(&user));
+    }
+
+    #[test]
+    #[should_panic]
+    fn non_admin_cannot_add_operator() {
+        let env = Env::default();
+        let (contract_id, client, _admin, user, non_admin) = setup(&env);
+
+        env.mock_auths(&[MockAuth {
+            address: &non_admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "add_operator",
+                args: (user.clone(),).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.add_operator(&user);
+    }
+
+    #[test]
+    #[should_panic]
+    fn non_admin_cannot_remove_operator() {
+        let env = Env::default();
+        let (contract_id, client, admin, _user, non_admin) = setup(&env);
+        let operator = add_operator(&env, &contract_id, &client, &admin);
+
+        env.mock_auths(&[MockAuth {
+            address: &non_admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "remove_operator",
+                args: (operator.clone(),).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.remove_operator(&operator);
+    }
+
+    #[test]
+    fn operator_can_credit() {
+        let env = Env::default();
+        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+        let operator = add_operator(&env, &contract_id, &client, &admin);
+
+        env.mock_auths(&[MockAuth {
+            address: &operator,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "credit_as_operator",
+                args: (operator.clone(), user.clone(), 100i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.credit_as_operator(&operator, &user, &100i128);
+        assert_eq!(client.balance(&user), 100i128);
+    }
+
+    #[test]
+    fn non_operator_cannot_credit() {
+        let env = Env::default();
+        let (contract_id, client, _admin, user, non_admin) = setup(&env);
+
+        env.mock_auths(&[MockAuth {
+            address: &non_admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "credit_as_operator",
+                args: (non_admin.clone(), user.clone(), 100i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        assert_eq!(
+            client.try_credit_as_operator(&non_admin, &user, &100i128),
+            Err(Ok(ContractError::NotOperator))
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn operator_cannot_debit() {
+        let env = Env::default();
+        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+        let operator = add_operator(&env, &contract_id, &client, &admin);
+
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "credit",
+                args: (user.clone(), 100i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.credit(&user, &100i128);
+
+        env.mock_auths(&[MockAuth {
+            address: &operator,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "debit",
+                args: (user.clone(), 50i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.debit(&user, &50i128);
+    }
+
+    #[test]
+    fn removed_operator_cannot_credit() {
+        let env = Env::default();
+        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+        let operator = add_operator(&env, &contract_id, &client, &admin);
+        env.mock_all_auths();
+        client.remove_operator(&operator);
+
+        assert_eq!(
+            client.try_credit_as_operator(&operator, &user, &100i128),
+            Err(Ok(ContractError::NotOperator))
+        );
+    }
+
+    #[test]
+    fn add_operator_emits_event() {
+        let env = Env::default();
+        let (contract_id, client, admin, _user, _non_admin) = setup(&env);
+        let operator = add_operator(&env, &contract_id, &client, &admin);
+
+        let events = env.events().all();
+        let event = events.last().unwrap();
+
+        let topics: soroban_sdk::Vec<soroban_sdk::Val> = event.1.clone();
+        assert_eq!(topics.len(), 1);
+        let event_name: Symbol = topics.get(0).unwrap().try_into_val(&env).unwrap();
+        assert_eq!(event_name, Symbol::new(&env, "add_operator"));
+
+        let data: Address = event.2.try_into_val(&env).unwrap();
+        assert_eq!(data, operator);
+    }
+
+    #[test]
+    fn remove_operator_emits_event() {
+        let env = Env::default();
+        let (contract_id, client, admin, _user, _non_admin) = setup(&env);
+        let operator = add_operator(&env, &contract_id, &client, &admin);
+
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "remove_operator",
+                args: (operator.clone(),).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.remove_operator(&operator);
+
+        let events = env.events().all();
+        let event = events.last().unwrap();
+
+        let topics: soroban_sdk::Vec<soroban_sdk::Val> = event.1.clone();
+        let event_name: Symbol = topics.get(0).unwrap().try_into_val(&env).unwrap();
+        assert_eq!(event_name, Symbol::new(&env, "remove_operator"));
+
+        let data: Address = event.2.try_into_val(&env).unwrap();
+        assert_eq!(data, operator);
+    }
+
+    // ============================================================================
+    // Token Custody Tests
+    // ============================================================================
+
+    #[test]
+    fn deposit_transfers_tokens_and_credits_balance() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let (token, token_client, asset_client) = setup_token(&env, &client);
+        asset_client.mint(&user, &1_000i128);
+
+        client.deposit(&user, &token, &400i128);
+
+        assert_eq!(client.balance(&user), 400i128);
+        assert_eq!(token_client.balance(&user), 600i128);
+        assert_eq!(token_client.balance(&contract_id), 400i128);
+    }
+
+    #[test]
+    fn deposit_fails_with_unconfigured_token() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        setup_token(&env, &client);
+        let other = env.register_stellar_asset_contract_v2(Address::generate(&env));
+        StellarAssetClient::new(&env, &other.address()).mint(&user, &1_000i128);
+
+        assert_eq!(
+            client.try_deposit(&user, &other.address(), &100i128),
+            Err(Ok(ContractError::UnsupportedToken))
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn deposit_fails_without_token_funds() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let (token, _token_client, _asset_client) = setup_token(&env, &client);
+
+        client.deposit(&user, &token, &100i128);
+    }
+
+    #[test]
+    fn withdraw_pays_out_tokens_and_debits_balance() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let (token, token_client, asset_client) = setup_token(&env, &client);
+        let recipient = Address::generate(&env);
+        asset_client.mint(&user, &1_000i128);
+        client.deposit(&user, &token, &500i128);
+
+        client.withdraw(&user, &200i128, &recipient);
+
+        assert_eq!(client.balance(&user), 300i128);
+        assert_eq!(token_client.balance(&recipient), 200i128);
+        assert_eq!(token_client.balance(&contract_id), 300i128);
+    }
+
+    #[test]
+    fn withdraw_fails_with_insufficient_balance() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let (token, _token_client, asset_client) = setup_token(&env, &client);
+        asset_client.mint(&user, &1_000i128);
+        client.deposit(&user, &token, &100i128);
+
+        assert_eq!(
+            client.try_withdraw(&user, &101i128, &user),
+            Err(Ok(ContractError::InsufficientBalance))
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn withdraw_requires_user_auth() {
+        let env = Env::default();
+        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "withdraw",
+                args: (user.clone(), 10i128, admin.clone()).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+
+        client.withdraw(&user, &10i128, &admin);
+    }
+
+    // ============================================================================
+    // Storage Layout Tests
+    // ============================================================================
+
+    #[test]
+    fn credit_writes_per_user_persistent_entry_with_ttl() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (contract_id, client, _admin, user, _non_admin) = setup(&env);
+
+        client.credit(&user, &100i128);
+
+        env.as_contract(&contract_id, || {
+            let key = DataKey::Balance(user.clone());
+            assert_eq!(env.storage().persistent().get::<_, i128>(&key), Some(100i128));
+            assert!(env.storage().persistent().get_ttl(&key) >= BALANCE_LIFETIME_THRESHOLD);
+            assert!(!env.storage().instance().has(&DataKey::Balances));
+        });
+    }
+
+    #[test]
+    fn migrate_balances_moves_legacy_map_entries() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (contract_id, client, _admin, user, non_admin) = setup(&env);
+        env.as_contract(&contract_id, || {
+            let mut legacy = Map::<Address, i128>::new(&env);
+            legacy.set(user.clone(), 250i128);
+            legacy.set(non_admin.clone(), 40i128);
+            env.storage().instance().set(&DataKey::Balances, &legacy);
+        });
+        client.credit(&user, &10i128);
+
+        assert_eq!(client.migrate_balances(), 2u32);
+
+        assert_eq!(client.balance(&user), 260i128);
+        assert_eq!(client.balance(&non_admin), 40i128);
+        env.as_contract(&contract_id, || {
+            assert!(!env.storage().instance().has(&DataKey::Balances));
+        });
+    }
+
+    #[test]
+    fn migrate_balances_runs_only_once() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (contract_id, client, _admin, user, _non_admin) = setup(&env);
+        env.as_contract(&contract_id, || {
+            let mut legacy = Map::<Address, i128>::new(&env);
+            legacy.set(user.clone(), 250i128);
+            env.storage().instance().set(&DataKey::Balances, &legacy);
+        });
+
+        client.migrate_balances();
+        assert_eq!(
+            client.try_migrate_balances(),
+            Err(Ok(ContractError::NoLegacyBalances))
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn non_admin_cannot_migrate_balances() {
+        let env = Env::default();
+        let (contract_id, client, _admin, _user, non_admin) = setup(&env);
+
+        env.mock_auths(&[MockAuth {
+            address: &non_admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "migrate_balances",
+                args: ().into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.migrate_balances();
+    }
+
+    // ============================================================================
+    // Debit Cap Tests
+    // ============================================================================
+
+    const DAY: u64 = 86_400;
+
+    fn cap(limit: i128) -> Option<DebitCap> {
+        Some(DebitCap {
+            limit,
+            period_secs: DAY,
+        })
+    }
+
+    #[test]
+    fn debit_within_cap_succeeds_and_tracks_usage() {
+        let env = Env::default();
+        env.mock_all_auths();
+        env.ledger().set_timestamp(10 * DAY + 5);
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        client.credit(&user, &1_000i128);
+        client.set_debit_cap(&user, &cap(300));
+
+        client.debit(&user, &200i128);
+        client.debit(&user, &100i128);
+
+        assert_eq!(client.balance(&user), 700i128);
+        assert_eq!(
+            client.cap_usage(&user),
+            Some(CapUsage {
+                period_start: 10 * DAY,
+                spent: 300,
+            })
+        );
+    }
+
+    #[test]
+    fn debit_over_cap_fails_with_typed_error() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        client.credit(&user, &1_000i128);
+        client.set_debit_cap(&user, &cap(300));
+        client.debit(&user, &250i128);
+
+        let result = client.try_debit(&user, &51i128);
+        assert_eq!(result, Err(Ok(ContractError::DebitCapExceeded)));
+        assert_eq!(client.balance(&user), 750i128);
+    }
+
+    #[test]
+    fn cap_resets_in_next_period() {
+        let env = Env::default();
+        env.mock_all_auths();
+        env.ledger().set_timestamp(DAY);
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        client.credit(&user, &1_000i128);
+        client.set_debit_cap(&user, &cap(300));
+        client.debit(&user, &300i128);
+
+        env.ledger().set_timestamp(2 * DAY);
+        assert_eq!(client.cap_usage(&user).unwrap().spent, 0i128);
+        client.debit(&user, &300i128);
+        assert_eq!(client.balance(&user), 400i128);
+    }
+
+    #[test]
+    fn default_cap_applies_until_user_cap_overrides_it() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        client.credit(&user, &1_000i128);
+        client.set_default_debit_cap(&cap(100));
+
+        assert_eq!(client.debit_cap(&user), cap(100));
+        assert_eq!(
+            client.try_debit(&user, &101i128),
+            Err(Ok(ContractError::DebitCapExceeded))
+        );
+
+        client.set_debit_cap(&user, &cap(500));
+        client.debit(&user, &400i128);
+
+        client.set_debit_cap(&user, &None);
+        assert_eq!(client.debit_cap(&user), cap(100));
+    }
+
+    #[test]
+    fn withdraw_counts_against_cap() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let (token, _token_client, asset_client) = setup_token(&env, &client);
+        asset_client.mint(&user, &1_000i128);
+        client.deposit(&user, &token, &1_000i128);
+        client.set_debit_cap(&user, &cap(100));
+
+        assert_eq!(
+            client.try_withdraw(&user, &150i128, &user),
+            Err(Ok(ContractError::DebitCapExceeded))
+        );
+    }
+
+    #[test]
+    fn cap_usage_is_none_without_cap() {
+        let env = Env::default();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        assert_eq!(client.debit_cap(&user), None);
+        assert_eq!(client.cap_usage(&user), None);
+    }
+
+    #[test]
+    fn set_debit_cap_rejects_zero_period() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let invalid = Some(DebitCap {
+            limit: 100,
+            period_secs: 0,
+        });
+        assert_eq!(
+            client.try_set_debit_cap(&user, &invalid),
+            Err(Ok(ContractError::InvalidDebitCap))
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn non_admin_cannot_set_debit_cap() {
+        let env = Env::default();
+        let (contract_id, client, _admin, user, non_admin) = setup(&env);
+
+        env.mock_auths(&[MockAuth {
+            address: &non_admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "set_debit_cap",
+                args: (user.clone(), cap(0)).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.set_debit_cap(&user, &cap(0));
+    }
+
+    // ============================================================================
+    // Error Code Tests
+    // ============================================================================
+
+    #[test]
+    fn contract_error_codes() {
+        assert_eq!(ContractError::DebitCapExceeded as u32, 1);
+        assert_eq!(ContractError::AlreadyInitialized as u32, 2);
+        assert_eq!(ContractError::NotInitialized as u32, 3);
+        assert_eq!(ContractError::ContractPaused as u32, 4);
+        assert_eq!(ContractError::InvalidAmount as u32, 5);
+        assert_eq!(ContractError::InsufficientBalance as u32, 6);
+        assert_eq!(ContractError::Overflow as u32, 7);
+        assert_eq!(ContractError::NotOperator as u32, 8);
+        assert_eq!(ContractError::TokenNotSet as u32, 9);
+        assert_eq!(ContractError::UnsupportedToken as u32, 10);
+        assert_eq!(ContractError::NoLegacyBalances as u32, 11);
+        assert_eq!(ContractError::InvalidDebitCap as u32, 12);
+        assert_eq!(ContractError::InvalidExpiry as u32, 13);
+        assert_eq!(ContractError::NoPendingAdmin as u32, 14);
+        assert_eq!(ContractError::AdminTransferExpired as u32, 15);
+        assert_eq!(ContractError::DuplicateHold as u32, 16);
+        assert_eq!(ContractError::HoldNotFound as u32, 17);
+        assert_eq!(ContractError::HoldExpired as u32, 18);
+        assert_eq!(ContractError::CaptureExceedsHold as u32, 19);
+        assert_eq!(ContractError::SelfTransfer as u32, 20);
+        assert_eq!(ContractError::InvalidLimit as u32, 21);
+        assert_eq!(ContractError::DuplicateReference as u32, 22);
+        assert_eq!(ContractError::AccountFrozen as u32, 23);
+        assert_eq!(ContractError::AccountNotFrozen as u32, 24);
+        assert_eq!(ContractError::InvalidPauseFlags as u32, 25);
+        assert_eq!(ContractError::AlreadyMigrated as u32, 26);
+        assert_eq!(ContractError::InvalidTtlConfig as u32, 27);
+        assert_eq!(ContractError::InsufficientAllowance as u32, 28);
+        assert_eq!(ContractError::InvalidSchedule as u32, 29);
+        assert_eq!(ContractError::StandingOrderNotFound as u32, 30);
+        assert_eq!(ContractError::StandingOrderNotDue as u32, 31);
+        assert_eq!(ContractError::InvalidFeeConfig as u32, 32);
+        assert_eq!(ContractError::InvalidBucket as u32, 33);
+        assert_eq!(ContractError::InvalidQueueConfig as u32, 34);
+        assert_eq!(ContractError::WithdrawalNotFound as u32, 35);
+        assert_eq!(ContractError::WithdrawalNotReady as u32, 36);
+        assert_eq!(ContractError::InvalidBreakerConfig as u32, 37);
+        assert_eq!(ContractError::TokenInUse as u32, 38);
+        assert_eq!(ContractError::AssetCapNotSet as u32, 39);
+    }
+
+    #[test]
+    fn credit_fails_on_balance_overflow() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        client.credit(&user, &i128::MAX);
+
+        assert_eq!(
+            client.try_credit(&user, &1i128),
+            Err(Ok(ContractError::Overflow))
+        );
+        assert_eq!(client.balance(&user), i128::MAX);
+    }
+
+    #[test]
+    fn credit_fails_when_wallet_total_overflows() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, non_admin) = setup(&env);
+        client.credit(&user, &i128::MAX);
+
+        assert_eq!(
+            client.try_credit(&non_admin, &1i128),
+            Err(Ok(ContractError::Overflow))
+        );
+        assert_eq!(client.balance(&non_admin), 0i128);
+    }
+
+    #[test]
+    fn credit_fails_before_init() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let contract_id = env.register(RentWallet, ());
+        let client = RentWalletClient::new(&env, &contract_id);
+        let user = Address::generate(&env);
+
+        assert_eq!(
+            client.try_credit(&user, &1i128),
+            Err(Ok(ContractError::NotInitialized))
+        );
+    }
+
+    #[test]
+    fn set_token_rejects_change_while_balances_outstanding() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let (token, _token_client, asset_client) = setup_token(&env, &client);
+        let other = env
+            .register_stellar_asset_contract_v2(Address::generate(&env))
+            .address();
+        asset_client.mint(&user, &1_000i128);
+        client.deposit(&user, &token, &500i128);
+
+        assert_eq!(
+            client.try_set_token(&other),
+            Err(Ok(ContractError::TokenInUse))
+        );
+        client.set_token(&token);
+
+        client.withdraw(&user, &500i128, &user);
+        client.set_token(&other);
+        assert_eq!(client.token(), Some(other));
+    }
+
+    #[test]
+    fn withdraw_fails_without_configured_token() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        client.credit(&user, &100i128);
+
+        assert_eq!(
+            client.try_withdraw(&user, &10i128, &user),
+            Err(Ok(ContractError::TokenNotSet))
+        );
+    }
+
+    #[test]
+    fn propose_admin_rejects_past_expiry() {
+        let env = Env::default();
+        env.mock_all_auths();
+        env.ledger().set_timestamp(1_000);
+        let (_contract_id, client, _admin, _user, _non_admin) = setup(&env);
+
+        assert_eq!(
+            client.try_propose_admin(&Address::generate(&env), &Some(1_000u64)),
+            Err(Ok(ContractError::InvalidExpiry))
+        );
+    }
+
+    // ============================================================================
+    // Hold Tests
+    // ============================================================================
+
+    fn hold_id(env: &Env, n: u8) -> BytesN<32> {
+        BytesN::from_array(env, &[n; 32])
+    }
+
+    #[test]
+    fn hold_reserves_available_balance() {
+        let env = Env::default();
+        env.mock_all_auths();
+        env.ledger().set_timestamp(1_000);
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        client.credit(&user, &500i128);
+
+        client.hold(&user, &200i128, &hold_id(&env, 1), &2_000u64);
+
+        assert_eq!(client.balance(&user), 500i128);
+        assert_eq!(client.available_balance(&user), 300i128);
+        assert_eq!(
+            client.get_hold(&hold_id(&env, 1)),
+            Some(Hold {
+                user: user.clone(),
+                amount: 200,
+                expires_at: 2_000,
+            })
+        );
+    }
+
+    #[test]
+    fn held_funds_cannot_be_debited() {
+        let env = Env::default();
+        env.mock_all_auths();
+        env.ledger().set_timestamp(1_000);
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        client.credit(&user, &500i128);
+        client.hold(&user, &200i128, &hold_id(&env, 1), &2_000u64);
+
+        assert_eq!(
+            client.try_debit(&user, &301i128),
+            Err(Ok(ContractError::InsufficientBalance))
+        );
+        client.debit(&user, &300i128);
+        assert_eq!(client.available_balance(&user), 0i128);
+    }
+
+    #[test]
+    fn hold_fails_when_exceeding_available_balance() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        client.credit(&user, &500i128);
+        client.hold(&user, &400i128, &hold_id(&env, 1), &2_000u64);
+
+        assert_eq!(
+            client.try_hold(&user, &101i128, &hold_id(&env, 2), &2_000u64),
+            Err(Ok(ContractError::InsufficientBalance))
+        );
+    }
+
+    #[test]
+    fn hold_rejects_duplicate_id_and_past_expiry() {
+        let env = Env::default();
+        env.mock_all_auths();
+        env.ledger().set_timestamp(1_000);
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        client.credit(&user, &500i128);
+        client.hold(&user, &100i128, &hold_id(&env, 1), &2_000u64);
+
+        assert_eq!(
+            client.try_hold(&user, &100i128, &hold_id(&env, 1), &2_000u64),
+            Err(Ok(ContractError::DuplicateHold))
+        );
+        assert_eq!(
+            client.try_hold(&user, &100i128, &hold_id(&env, 2), &1_000u64),
+            Err(Ok(ContractError::InvalidExpiry))
+        );
+    }
+
+    #[test]
+    fn capture_debits_and_releases_remainder() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        client.credit(&user, &500i128);
+        client.hold(&user, &200i128, &hold_id(&env, 1), &2_000u64);
+
+        client.capture(&hold_id(&env, 1), &150i128);
+
+        assert_eq!(client.balance(&user), 350i128);
+        assert_eq!(client.available_balance(&user), 350i128);
+        assert_eq!(client.get_hold(&hold_id(&env, 1)), None);
+        assert_eq!(
+            client.try_capture(&hold_id(&env, 1), &50i128),
+            Err(Ok(ContractError::HoldNotFound))
+        );
+    }
+
+    #[test]
+    fn capture_cannot_exceed_hold() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        client.credit(&user, &500i128);
+        client.hold(&user, &200i128, &hold_id(&env, 1), &2_000u64);
+
+        assert_eq!(
+            client.try_capture(&hold_id(&env, 1), &201i128),
+            Err(Ok(ContractError::CaptureExceedsHold))
+        );
+    }
+
+    #[test]
+    fn release_restores_available_balance() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        client.credit(&user, &500i128);
+        client.hold(&user, &200i128, &hold_id(&env, 1), &2_000u64);
+
+        client.release(&hold_id(&env, 1));
+
+        assert_eq!(client.balance(&user), 500i128);
+        assert_eq!(client.available_balance(&user), 500i128);
+        assert_eq!(client.get_hold(&hold_id(&env, 1)), None);
+    }
+
+    #[test]
+    fn expired_hold_stops_reserving_funds() {
+        let env = Env::default();
+        env.mock_all_auths();
+        env.ledger().set_timestamp(1_000);
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        client.credit(&user, &500i128);
+        client.hold(&user, &200i128, &hold_id(&env, 1), &2_000u64);
+
+        env.ledger().set_timestamp(2_001);
+
+        assert_eq!(client.available_balance(&user), 500i128);
+        assert_eq!(
+            client.try_capture(&hold_id(&env, 1), &100i128),
+            Err(Ok(ContractError::HoldExpired))
+        );
+        client.debit(&user, &500i128);
+        assert_eq!(client.balance(&user), 0i128);
+    }
+
+    #[test]
+    fn new_hold_prunes_expired_holds() {
+        let env = Env::default();
+        env.mock_all_auths();
+        env.ledger().set_timestamp(1_000);
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        client.credit(&user, &500i128);
+        client.hold(&user, &400i128, &hold_id(&env, 1), &2_000u64);
+
+        env.ledger().set_timestamp(3_000);
+        client.hold(&user, &400i128, &hold_id(&env, 2), &4_000u64);
+
+        assert_eq!(client.get_hold(&hold_id(&env, 1)), None);
+        assert_eq!(client.available_balance(&user), 100i128);
+    }
+
+    #[test]
+    #[should_panic]
+    fn non_admin_cannot_hold() {
+        let env = Env::default();
+        let (contract_id, client, _admin, user, non_admin) = setup(&env);
+        let id = hold_id(&env, 1);
+
+        env.mock_auths(&[MockAuth {
+            address: &non_admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "hold",
+                args: (user.clone(), 100i128, id.clone(), 2_000u64).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.hold(&user, &100i128, &id, &2_000u64);
+    }
+
+    // ============================================================================
+    // Transfer Tests
+    // ============================================================================
+
+    #[test]
+    fn transfer_moves_balance_between_users() {
+        let env = Env::default();
+        let (contract_id, client, admin, user, landlord) = setup(&env);
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "credit",
+                args: (user.clone(), 500i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.credit(&user, &500i128);
+
+        env.mock_auths(&[MockAuth {
+            address: &user,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "transfer",
+                args: (user.clone(), landlord.clone(), 200i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.transfer(&user, &landlord, &200i128);
+
+        assert_eq!(client.balance(&user), 300i128);
+        assert_eq!(client.balance(&landlord), 200i128);
+    }
+
+    #[test]
+    #[should_panic]
+    fn transfer_requires_sender_auth() {
+        let env = Env::default();
+        let (contract_id, client, admin, user, landlord) = setup(&env);
+        env.mock_all_auths();
+        client.credit(&user, &500i128);
+
+        env.mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "transfer",
+                args: (user.clone(), landlord.clone(), 200i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.transfer(&user, &landlord, &200i128);
+    }
+
+    #[test]
+    fn transfer_fails_with_insufficient_balance() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, landlord) = setup(&env);
+        client.credit(&user, &100i128);
+
+        assert_eq!(
+            client.try_transfer(&user, &landlord, &101i128),
+            Err(Ok(ContractError::InsufficientBalance))
+        );
+        assert_eq!(client.balance(&landlord), 0i128);
+    }
+
+    #[test]
+    fn transfer_fails_when_paused() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, landlord) = setup(&env);
+        client.credit(&user, &100i128);
+        client.pause();
+
+        assert_eq!(
+            client.try_transfer(&user, &landlord, &50i128),
+            Err(Ok(ContractError::ContractPaused))
+        );
+    }
+
+    #[test]
+    fn transfer_rejects_self_transfer_and_invalid_amount() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, landlord) = setup(&env);
+        client.credit(&user, &100i128);
+
+        assert_eq!(
+            client.try_transfer(&user, &user, &50i128),
+            Err(Ok(ContractError::SelfTransfer))
+        );
+        assert_eq!(
+            client.try_transfer(&user, &landlord, &0i128),
+            Err(Ok(ContractError::InvalidAmount))
+        );
+    }
+
+    #[test]
+    fn transfer_emits_event_with_both_balances() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, landlord) = setup(&env);
+        client.credit(&user, &500i128);
+        client.credit(&landlord, &10i128);
+
+        client.transfer(&user, &landlord, &200i128);
+
+        let event = env.events().all().last().unwrap();
+        let topics: soroban_sdk::Vec<soroban_sdk::Val> = event.1.clone();
+        assert_eq!(topics.len(), 3);
+        let event_name: Symbol = topics.get(0).unwrap().try_into_val(&env).unwrap();
+        assert_eq!(event_name, Symbol::new(&env, "transfer"));
+        let from: Address = topics.get(1).unwrap().try_into_val(&env).unwrap();
+        assert_eq!(from, user);
+        let to: Address = topics.get(2).unwrap().try_into_val(&env).unwrap();
+        assert_eq!(to, landlord);
+
+        let data: (i128, i128, i128) = event.2.try_into_val(&env).unwrap();
+        assert_eq!(data, (200i128, 300i128, 210i128));
+    }
+
+    // ============================================================================
+    // Journal Tests
+    // ============================================================================
+
+    #[test]
+    fn journal_records_every_balance_movement() {
+        let env = Env::default();
+        env.mock_all_auths();
+        env.ledger().set_timestamp(1_000);
+        let (_contract_id, client, _admin, user, landlord) = setup(&env);
+
+        client.credit(&user, &500i128);
+        env.ledger().set_timestamp(1_100);
+        client.debit(&user, &100i128);
+        client.transfer(&user, &landlord, &50i128);
+
+        let page = client.list_entries(&user, &None, &10u32);
+        assert_eq!(page.entries.len(), 3);
+        assert!(!page.has_next);
+        assert_eq!(
+            page.entries.get(0).unwrap(),
+            JournalEntry {
+                seq: 0,
+                kind: Symbol::new(&env, "credit"),
+                amount: 500,
+                balance: 500,
+                timestamp: 1_000,
+                reference: None,
+                asset: None,
+            }
+        );
+        assert_eq!(page.entries.get(1).unwrap().kind, Symbol::new(&env, "debit"));
+        assert_eq!(page.entries.get(1).unwrap().balance, 400i128);
+        assert_eq!(page.entries.get(1).unwrap().timestamp, 1_100u64);
+        assert_eq!(page.entries.get(2).unwrap().kind, Symbol::new(&env, "transfer_out"));
+        assert_eq!(page.entries.get(2).unwrap().balance, 350i128);
+
+        let landlord_page = client.list_entries(&landlord, &None, &10u32);
+        assert_eq!(landlord_page.entries.len(), 1);
+        assert_eq!(
+            landlord_page.entries.get(0).unwrap().kind,
+            Symbol::new(&env, "transfer_in")
+        );
+    }
+
+    #[test]
+    fn journal_paginates_without_skipping_or_duplicates() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        for i in 1..=7 {
+            client.credit(&user, &(i as i128));
+        }
+        assert_eq!(client.entry_count(&user), 7u64);
+
+        let page1 = client.list_entries(&user, &None, &3u32);
+        assert_eq!(page1.entries.len(), 3);
+        assert!(page1.has_next);
+        assert_eq!(page1.next_cursor, 2u64);
+
+        let page2 = client.list_entries(&user, &Some(page1.next_cursor), &3u32);
+        assert_eq!(page2.entries.len(), 3);
+        assert!(page2.has_next);
+        assert_eq!(page2.entries.get(0).unwrap().seq, 3u64);
+
+        let page3 = client.list_entries(&user, &Some(page2.next_cursor), &3u32);
+        assert_eq!(page3.entries.len(), 1);
+        assert!(!page3.has_next);
+        assert_eq!(page3.entries.get(0).unwrap().seq, 6u64);
+        assert_eq!(page3.entries.get(0).unwrap().balance, 28i128);
+    }
+
+    #[test]
+    fn journal_records_hold_id_as_capture_reference() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        client.credit(&user, &500i128);
+        client.hold(&user, &200i128, &hold_id(&env, 9), &2_000u64);
+        client.capture(&hold_id(&env, 9), &200i128);
+
+        let page = client.list_entries(&user, &Some(0u64), &10u32);
+        assert_eq!(page.entries.len(), 1);
+        let entry = page.entries.get(0).unwrap();
+        assert_eq!(entry.kind, Symbol::new(&env, "capture"));
+        assert_eq!(entry.reference, Some(hold_id(&env, 9)));
+    }
+
+    #[test]
+    fn list_entries_is_empty_for_new_user() {
+        let env = Env::default();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+
+        let page = client.list_entries(&user, &None, &10u32);
+        assert_eq!(page.entries.len(), 0);
+        assert!(!page.has_next);
+        assert_eq!(client.list_entries(&user, &Some(5u64), &10u32).entries.len(), 0);
+    }
+
+    #[test]
+    fn list_entries_rejects_invalid_limit() {
+        let env = Env::default();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+
+        assert_eq!(
+            client.try_list_entries(&user, &None, &0u32),
+            Err(Ok(ContractError::InvalidLimit))
+        );
+        assert_eq!(
+            client.try_list_entries(&user, &None, &101u32),
+            Err(Ok(ContractError::InvalidLimit))
+        );
+    }
+
+    // ============================================================================
+    // Idempotency Tests
+    // ============================================================================
+
+    #[test]
+    fn credit_with_ref_rejects_replay() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let reference = BytesN::from_array(&env, &[7u8; 32]);
+
+        client.credit_with_ref(&user, &100i128, &reference);
+        assert_eq!(
+            client.try_credit_with_ref(&user, &100i128, &reference),
+            Err(Ok(ContractError::DuplicateReference))
+        );
+
+        assert_eq!(client.balance(&user), 100i128);
+        assert!(client.is_processed(&reference));
+    }
+
+    #[test]
+    fn debit_with_ref_rejects_replay() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let reference = BytesN::from_array(&env, &[8u8; 32]);
+        client.credit(&user, &500i128);
+
+        client.debit_with_ref(&user, &100i128, &reference);
+        assert_eq!(
+            client.try_debit_with_ref(&user, &100i128, &reference),
+            Err(Ok(ContractError::DuplicateReference))
+        );
+
+        assert_eq!(client.balance(&user), 400i128);
+    }
+
+    #[test]
+    fn references_share_one_namespace() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let reference = BytesN::from_array(&env, &[9u8; 32]);
+        client.credit_with_ref(&user, &100i128, &reference);
+
+        assert_eq!(
+            client.try_debit_with_ref(&user, &50i128, &reference),
+            Err(Ok(ContractError::DuplicateReference))
+        );
+    }
+
+    #[test]
+    fn failed_call_does_not_consume_reference() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let reference = BytesN::from_array(&env, &[10u8; 32]);
+
+        assert_eq!(
+            client.try_debit_with_ref(&user, &50i128, &reference),
+            Err(Ok(ContractError::InsufficientBalance))
+        );
+        assert!(!client.is_processed(&reference));
+
+        client.credit(&user, &50i128);
+        client.debit_with_ref(&user, &50i128, &reference);
+        assert_eq!(client.balance(&user), 0i128);
+    }
+
+    #[test]
+    fn processed_reference_is_journaled_and_kept_alive() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let reference = BytesN::from_array(&env, &[11u8; 32]);
+
+        client.credit_with_ref(&user, &100i128, &reference);
+
+        let entry = client.list_entries(&user, &None, &1u32).entries.get(0).unwrap();
+        assert_eq!(entry.reference, Some(reference.clone()));
+        env.as_contract(&contract_id, || {
+            let ttl = env
+                .storage()
+                .persistent()
+                .get_ttl(&DataKey::ProcessedRef(reference.clone()));
+            assert!(ttl >= BALANCE_LIFETIME_THRESHOLD);
+        });
+    }
+
+    // ============================================================================
+    // Multi-Asset Tests
+    // ============================================================================
+
+    #[test]
+    fn asset_balances_are_tracked_per_asset() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let (usdc, _token_client, _asset_client) = setup_token(&env, &client);
+        let ngn = Address::generate(&env);
+        client.add_asset(&ngn);
+
+        client.credit_asset(&user, &ngn, &5_000i128);
+        client.credit_asset(&user, &usdc, &30i128);
+        client.debit_asset(&user, &ngn, &1_000i128);
+
+        assert_eq!(client.balance_asset(&user, &ngn), 4_000i128);
+        assert_eq!(client.balance_asset(&user, &usdc), 30i128);
+        // Existing calls keep working against the default asset
+        assert_eq!(client.balance(&user), 30i128);
+        client.credit(&user, &20i128);
+        assert_eq!(client.balance_asset(&user, &usdc), 50i128);
+        assert_eq!(client.balance_asset(&user, &ngn), 4_000i128);
+    }
+
+    #[test]
+    fn credit_asset_rejects_unsupported_asset() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        setup_token(&env, &client);
+        let unknown = Address::generate(&env);
+
+        assert_eq!(
+            client.try_credit_asset(&user, &unknown, &100i128),
+            Err(Ok(ContractError::UnsupportedToken))
+        );
+    }
+
+    #[test]
+    fn debit_asset_fails_with_insufficient_balance() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let ngn = Address::generate(&env);
+        client.add_asset(&ngn);
+        client.credit_asset(&user, &ngn, &100i128);
+
+        assert_eq!(
+            client.try_debit_asset(&user, &ngn, &101i128),
+            Err(Ok(ContractError::InsufficientBalance))
+        );
+    }
+
+    #[test]
+    fn asset_debits_count_against_asset_cap() {
+        let env = Env::default();
+        env.mock_all_auths();
+        env.ledger().set_timestamp(DAY);
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let ngn = Address::generate(&env);
+        client.add_asset(&ngn);
+        client.credit_asset(&user, &ngn, &1_000i128);
+        client.set_asset_debit_cap(&user, &ngn, &cap(300));
+
+        client.debit_asset(&user, &ngn, &200i128);
+        assert_eq!(
+            client.try_debit_asset(&user, &ngn, &101i128),
+            Err(Ok(ContractError::DebitCapExceeded))
+        );
+        assert_eq!(
+            client.asset_cap_usage(&user, &ngn),
+            Some(CapUsage {
+                period_start: DAY,
+                spent: 200,
+            })
+        );
+        // The default-asset cap is tracked separately.
+        assert_eq!(client.cap_usage(&user), None);
+        assert_eq!(client.balance_asset(&user, &ngn), 800i128);
+    }
+
+    #[test]
+    fn capped_user_needs_asset_cap_for_other_assets() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let ngn = Address::generate(&env);
+        client.add_asset(&ngn);
+        client.credit_asset(&user, &ngn, &1_000i128);
+        client.set_default_debit_cap(&cap(10));
+
+        assert_eq!(
+            client.try_debit_asset(&user, &ngn, &1_000i128),
+            Err(Ok(ContractError::AssetCapNotSet))
+        );
+        assert_eq!(client.balance_asset(&user, &ngn), 1_000i128);
+
+        client.set_asset_debit_cap(&user, &ngn, &cap(1_000));
+        client.debit_asset(&user, &ngn, &1_000i128);
+        assert_eq!(client.balance_asset(&user, &ngn), 0i128);
+    }
+
+    #[test]
+    fn removed_asset_can_only_be_debited() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let ngn = Address::generate(&env);
+        client.add_asset(&ngn);
+        client.add_asset(&ngn);
+        assert_eq!(client.supported_assets().len(), 1);
+        client.credit_asset(&user, &ngn, &100i128);
+
+        client.remove_asset(&ngn);
+
+        assert_eq!(client.supported_assets().len(), 0);
+        assert_eq!(
+            client.try_credit_asset(&user, &ngn, &1i128),
+            Err(Ok(ContractError::UnsupportedToken))
+        );
+        client.debit_asset(&user, &ngn, &100i128);
+        assert_eq!(client.balance_asset(&user, &ngn), 0i128);
+    }
+
+    #[test]
+    fn asset_movements_are_journaled_with_asset() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let ngn = Address::generate(&env);
+        client.add_asset(&ngn);
+
+        client.credit_asset(&user, &ngn, &100i128);
+
+        let entry = client.list_entries(&user, &None, &1u32).entries.get(0).unwrap();
+        assert_eq!(entry.asset, Some(ngn));
+        assert_eq!(entry.balance, 100i128);
+    }
+
+    #[test]
+    #[should_panic]
+    fn non_admin_cannot_add_asset() {
+        let env = Env::default();
+        let (contract_id, client, _admin, _user, non_admin) = setup(&env);
+        let ngn = Address::generate(&env);
+
+        env.mock_auths(&[MockAuth {
+            address: &non_admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "add_asset",
+                args: (ngn.clone(),).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.add_asset(&ngn);
+    }
+
+    // ============================================================================
+    // Freeze Tests
+    // ============================================================================
+
+    #[test]
+    fn frozen_account_rejects_credit_and_debit() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        client.credit(&user, &100i128);
 
-        let client = RentWalletClient::new(env, &contract_id);
+        client.freeze(&user, &3u32);
+
+        assert!(client.is_frozen(&user));
+        assert_eq!(client.frozen_reason(&user), Some(3u32));
+        assert_eq!(
+            client.try_credit(&user, &10i128),
+            Err(Ok(ContractError::AccountFrozen))
+        );
+        assert_eq!(
+            client.try_debit(&user, &10i128),
+            Err(Ok(ContractError::AccountFrozen))
+        );
+        assert_eq!(client.balance(&user), 100i128);
+    }
 
-        let admin = Address::generate(env);
+    #[test]
+    fn freeze_only_affects_one_account() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, other) = setup(&env);
+        client.credit(&user, &100i128);
+        client.credit(&other, &100i128);
 
-        let user = Address::generate(env);
+        client.freeze(&user, &1u32);
 
-        let non_admin = Address::generate(env);
+        client.debit(&other, &50i128);
+        assert_eq!(client.balance(&other), 50i128);
+        assert!(!client.is_frozen(&other));
+    }
 
-        client.init(&admin);
+    #[test]
+    fn frozen_account_cannot_send_or_receive_transfers() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, other) = setup(&env);
+        client.credit(&user, &100i128);
+        client.credit(&other, &100i128);
+        client.freeze(&user, &1u32);
+
+        assert_eq!(
+            client.try_transfer(&user, &other, &10i128),
+            Err(Ok(ContractError::AccountFrozen))
+        );
+        assert_eq!(
+            client.try_transfer(&other, &user, &10i128),
+            Err(Ok(ContractError::AccountFrozen))
+        );
+    }
 
-        (contract_id, client, admin, user, non_admin)
+    #[test]
+    fn frozen_account_rejects_asset_movements_and_holds() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let ngn = Address::generate(&env);
+        client.add_asset(&ngn);
+        client.credit(&user, &100i128);
+        client.freeze(&user, &1u32);
+
+        assert_eq!(
+            client.try_credit_asset(&user, &ngn, &10i128),
+            Err(Ok(ContractError::AccountFrozen))
+        );
+        assert_eq!(
+            client.try_hold(&user, &10i128, &hold_id(&env, 1), &2_000u64),
+            Err(Ok(ContractError::AccountFrozen))
+        );
+    }
+
+    #[test]
+    fn unfreeze_restores_access() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        client.freeze(&user, &7u32);
+
+        client.unfreeze(&user);
+
+        assert!(!client.is_frozen(&user));
+        client.credit(&user, &10i128);
+        assert_eq!(client.balance(&user), 10i128);
+        assert_eq!(
+            client.try_unfreeze(&user),
+            Err(Ok(ContractError::AccountNotFrozen))
+        );
+    }
+
+    #[test]
+    fn freeze_and_unfreeze_emit_reason_code() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
 
+        client.freeze(&user, &42u32);
+        let event = env.events().all().last().unwrap();
+        let event_name: Symbol = event.1.get(0).unwrap().try_into_val(&env).unwrap();
+        assert_eq!(event_name, Symbol::new(&env, "freeze"));
+        let event_user: Address = event.1.get(1).unwrap().try_into_val(&env).unwrap();
+        assert_eq!(event_user, user);
+        let reason: u32 = event.2.try_into_val(&env).unwrap();
+        assert_eq!(reason, 42u32);
+
+        client.unfreeze(&user);
+        let event = env.events().all().last().unwrap();
+        let event_name: Symbol = event.1.get(0).unwrap().try_into_val(&env).unwrap();
+        assert_eq!(event_name, Symbol::new(&env, "unfreeze"));
+        let reason: u32 = event.2.try_into_val(&env).unwrap();
+        assert_eq!(reason, 42u32);
     }
 
+    #[test]
+    #[should_panic]
+    fn non_admin_cannot_freeze() {
+        let env = Env::default();
+        let (contract_id, client, _admin, user, non_admin) = setup(&env);
+
+        env.mock_auths(&[MockAuth {
+            address: &non_admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "freeze",
+                args: (user.clone(), 1u32).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.freeze(&user, &1u32);
+    }
 
     // ============================================================================
-    // Init Tests
+    // Granular Pause Tests
     // ============================================================================
 
     #[test]
-    fn init_sets_admin() {
+    fn pausing_debits_keeps_credits_open() {
         let env = Env::default();
-        let contract_id = env.register_contract(None, RentWallet);
-        let client = RentWalletClient::new(&env, &contract_id);
-        let admin = Address::generate(&env);
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, other) = setup(&env);
+        client.credit(&user, &100i128);
 
-        client.init(&admin);
+        client.pause_operations(&(PAUSE_DEBIT | PAUSE_WITHDRAW | PAUSE_TRANSFER));
+
+        client.credit(&user, &50i128);
+        assert_eq!(client.balance(&user), 150i128);
+        assert_eq!(
+            client.try_debit(&user, &10i128),
+            Err(Ok(ContractError::ContractPaused))
+        );
+        assert_eq!(
+            client.try_transfer(&user, &other, &10i128),
+            Err(Ok(ContractError::ContractPaused))
+        );
+        assert_eq!(
+            client.try_withdraw(&user, &10i128, &user),
+            Err(Ok(ContractError::ContractPaused))
+        );
+        assert!(client.is_paused());
+    }
+
+    #[test]
+    fn pause_flags_reports_full_state() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, _user, _non_admin) = setup(&env);
+        assert_eq!(client.pause_flags(), 0u32);
+
+        client.pause_operations(&PAUSE_TRANSFER);
+        client.pause_operations(&PAUSE_DEBIT);
+        assert_eq!(client.pause_flags(), PAUSE_DEBIT | PAUSE_TRANSFER);
+
+        client.unpause_operations(&PAUSE_TRANSFER);
+        assert_eq!(client.pause_flags(), PAUSE_DEBIT);
+
+        client.pause();
+        assert_eq!(client.pause_flags(), PAUSE_ALL);
+        client.unpause();
+        assert_eq!(client.pause_flags(), 0u32);
+        assert!(!client.is_paused());
+    }
+
+    #[test]
+    fn pausing_credits_keeps_debits_open() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        client.credit(&user, &100i128);
+
+        client.pause_operations(&PAUSE_CREDIT);
+
+        client.debit(&user, &40i128);
+        assert_eq!(client.balance(&user), 60i128);
+        assert_eq!(
+            client.try_credit(&user, &10i128),
+            Err(Ok(ContractError::ContractPaused))
+        );
+    }
+
+    #[test]
+    fn pause_operations_rejects_unknown_flags() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, _user, _non_admin) = setup(&env);
+
+        assert_eq!(
+            client.try_pause_operations(&(PAUSE_ALL + 1)),
+            Err(Ok(ContractError::InvalidPauseFlags))
+        );
+        assert_eq!(
+            client.try_unpause_operations(&(1u32 << 31)),
+            Err(Ok(ContractError::InvalidPauseFlags))
+        );
+    }
+
+    #[test]
+    fn pause_flag_changes_emit_events() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, _user, _non_admin) = setup(&env);
+        client.pause_operations(&PAUSE_DEBIT);
+
+        client.pause_operations(&PAUSE_WITHDRAW);
+
+        let event = env.events().all().last().unwrap();
+        let event_name: Symbol = event.1.get(0).unwrap().try_into_val(&env).unwrap();
+        assert_eq!(event_name, Symbol::new(&env, "pause_flags"));
+        let data: (u32, u32) = event.2.try_into_val(&env).unwrap();
+        assert_eq!(data, (PAUSE_DEBIT, PAUSE_DEBIT | PAUSE_WITHDRAW));
+    }
+
+    #[test]
+    fn legacy_paused_flag_pauses_everything() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (contract_id, client, _admin, user, _non_admin) = setup(&env);
+        env.as_contract(&contract_id, || {
+            env.storage().instance().set(&DataKey::Paused, &true);
+        });
+
+        assert_eq!(client.pause_flags(), PAUSE_ALL);
+        assert_eq!(
+            client.try_credit(&user, &10i128),
+            Err(Ok(ContractError::ContractPaused))
+        );
+
+        client.unpause_operations(&PAUSE_CREDIT);
+        assert_eq!(client.pause_flags(), PAUSE_ALL & !PAUSE_CREDIT);
+        client.credit(&user, &10i128);
+    }
+
+    #[test]
+    #[should_panic]
+    fn non_admin_cannot_pause_operations() {
+        let env = Env::default();
+        let (contract_id, client, _admin, _user, non_admin) = setup(&env);
 
-        // Admin should be able to perform admin operations
-        let user = Address::generate(&env);
         env.mock_auths(&[MockAuth {
-            address: &admin,
+            address: &non_admin,
             invoke: &MockAuthInvoke {
                 contract: &contract_id,
-                fn_name: "credit",
-                args: (user.clone(), 100i128).into_val(&env),
+                fn_name: "pause_operations",
+                args: (PAUSE_DEBIT,).into_val(&env),
                 sub_invokes: &[],
             },
         }]);
-        client.credit(&user, &100i128);
-        assert_eq!(client.balance(&user), 100i128);
+        client.pause_operations(&PAUSE_DEBIT);
+    }
+
+    // ============================================================================
+    // Upgrade & Migration Tests
+    // ============================================================================
+
+    /// Release build of the first upgradeable version of this contract
+    /// (commit 695346b), deployed as the build being upgraded from.
+    const PREVIOUS_WASM: &[u8] = include_bytes!("../test_fixtures/rent_wallet_previous.wasm");
+
+    /// Release build of the current tree, the upgrade target. `make wasm` in
+    /// `contracts/` builds it.
+    const CURRENT_WASM: &[u8] =
+        include_bytes!("../../target/wasm32v1-none/release/rent_wallet.wasm");
+
+    /// Deploys and initializes `PREVIOUS_WASM`. Release builds are not
+    /// size-optimized, so the budget limit is lifted for these tests.
+    fn setup_previous(env: &Env) -> (Address, RentWalletClient<'_>, Address) {
+        env.cost_estimate().budget().reset_unlimited();
+        let contract_id = env.register(PREVIOUS_WASM, ());
+        let client = RentWalletClient::new(env, &contract_id);
+        let admin = Address::generate(env);
+        client.init(&admin);
+        (contract_id, client, admin)
+    }
+
+    /// Rewinds storage to a deployment that predates schema versioning: no
+    /// version key, balances in the legacy map and the boolean pause flag.
+    fn seed_unversioned_storage(env: &Env, contract_id: &Address, user: &Address) {
+        env.as_contract(contract_id, || {
+            let mut legacy = Map::<Address, i128>::new(env);
+            legacy.set(user.clone(), 300i128);
+            env.storage().instance().set(&DataKey::Balances, &legacy);
+            env.storage().instance().set(&DataKey::Paused, &true);
+            env.storage().instance().remove(&DataKey::Version);
+        });
     }
 
     #[test]
-    fn init_initializes_empty_balances() {
+    fn init_records_schema_version() {
         let env = Env::default();
-        let contract_id = env.register_contract(None, RentWallet);
-        let client = RentWalletClient::new(&env, &contract_id);
-        let admin = Address::generate(&env);
+        let (_contract_id, client, _admin, _user, _non_admin) = setup(&env);
+        assert_eq!(client.version(), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn upgrade_from_previous_build_keeps_state() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin) = setup_previous(&env);
         let user = Address::generate(&env);
+        client.credit(&user, &500i128);
+        // The previous build has no circuit breaker.
+        assert!(client.try_window_outflow().is_err());
+
+        let wasm_hash = env.deployer().upload_contract_wasm(CURRENT_WASM);
+        client.upgrade(&wasm_hash);
+
+        let event = env.events().all().last().unwrap();
+        let event_name: Symbol = event.1.get(0).unwrap().try_into_val(&env).unwrap();
+        assert_eq!(event_name, Symbol::new(&env, "upgrade"));
+        let data: BytesN<32> = event.2.try_into_val(&env).unwrap();
+        assert_eq!(data, wasm_hash);
+
+        // Calls below run against the current build.
+        assert_eq!(client.balance(&user), 500i128);
+        assert_eq!(client.version(), SCHEMA_VERSION);
+        assert_eq!(client.window_outflow(), 0i128);
+        assert_eq!(client.debit(&user, &200i128), OutflowStatus::Executed);
+        assert_eq!(client.balance(&user), 300i128);
+    }
 
-        client.init(&admin);
+    #[test]
+    fn upgrade_then_migrate_moves_legacy_state() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (contract_id, client, _admin) = setup_previous(&env);
+        let user = Address::generate(&env);
+        seed_unversioned_storage(&env, &contract_id, &user);
+        assert_eq!(client.version(), 0u32);
+
+        let wasm_hash = env.deployer().upload_contract_wasm(CURRENT_WASM);
+        client.upgrade(&wasm_hash);
+        assert_eq!(client.migrate(), SCHEMA_VERSION);
+
+        assert_eq!(client.version(), SCHEMA_VERSION);
+        assert_eq!(client.balance(&user), 300i128);
+        assert_eq!(client.pause_flags(), PAUSE_ALL);
+        env.as_contract(&contract_id, || {
+            assert!(!env.storage().instance().has(&DataKey::Balances));
+            assert!(!env.storage().instance().has(&DataKey::Paused));
+        });
+    }
 
-        // Balance should be zero for any user initially
-        assert_eq!(client.balance(&user), 0i128);
+    #[test]
+    fn migrate_runs_only_once() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (contract_id, client, _admin, user, _non_admin) = setup(&env);
+        seed_unversioned_storage(&env, &contract_id, &user);
+
+        client.migrate();
+        let event = env.events().all().last().unwrap();
+        let data: (u32, u32) = event.2.try_into_val(&env).unwrap();
+        assert_eq!(data, (0u32, SCHEMA_VERSION));
+
+        assert_eq!(client.try_migrate(), Err(Ok(ContractError::AlreadyMigrated)));
+        assert_eq!(client.balance(&user), 300i128);
     }
 
     #[test]
-    #[should_panic(expected = "already initialized")]
-    fn init_cannot_be_called_twice() {
+    fn migrate_rejects_current_version() {
         let env = Env::default();
-        let contract_id = env.register_contract(None, RentWallet);
-        let client = RentWalletClient::new(&env, &contract_id);
-        let admin = Address::generate(&env);
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, _user, _non_admin) = setup(&env);
+        assert_eq!(client.try_migrate(), Err(Ok(ContractError::AlreadyMigrated)));
+    }
 
-        client.init(&admin);
-        client.init(&admin);
+    #[test]
+    #[should_panic]
+    fn non_admin_cannot_upgrade() {
+        let env = Env::default();
+        let (contract_id, client, _admin, _user, non_admin) = setup(&env);
+        env.cost_estimate().budget().reset_unlimited();
+        let wasm_hash = env.deployer().upload_contract_wasm(CURRENT_WASM);
+
+        env.mock_auths(&[MockAuth {
+            address: &non_admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "upgrade",
+                args: (wasm_hash.clone(),).into_val(&env),
+                sub_invokes: &[],
+            },
+        }]);
+        client.upgrade(&wasm_hash);
     }
 
     // ============================================================================
-    // Credit Tests
+    // TTL Management Tests
     // ============================================================================
 
+    fn advance_ledgers(env: &Env, ledgers: u32) {
+        env.ledger().with_mut(|li| li.sequence_number += ledgers);
+    }
+
     #[test]
-    fn credit_increases_balance() {
+    fn instance_ttl_is_extended_on_access() {
         let env = Env::default();
-        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+        env.mock_all_auths();
+        let (contract_id, client, _admin, user, _non_admin) = setup(&env);
+
+        client.credit(&user, &100i128);
+
+        assert_eq!(client.instance_ttl(), BALANCE_BUMP_AMOUNT);
+        env.as_contract(&contract_id, || {
+            assert_eq!(env.storage().instance().get_ttl(), BALANCE_BUMP_AMOUNT);
+        });
+        assert_eq!(client.balance_ttl(&user), BALANCE_BUMP_AMOUNT);
+    }
+
+    #[test]
+    fn custom_ttl_config_applies_to_new_entries() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let config = TtlConfig {
+            threshold: 1_000,
+            extend_to: 5_000,
+        };
+        client.set_ttl_config(&config);
+        assert_eq!(client.ttl_config(), config);
+
+        client.credit(&user, &100i128);
+
+        assert_eq!(client.balance_ttl(&user), 5_000);
+        env.as_contract(&contract_id, || {
+            let key = DataKey::Balance(user.clone());
+            assert_eq!(env.storage().persistent().get_ttl(&key), 5_000);
+        });
+    }
+
+    #[test]
+    fn access_only_extends_below_threshold() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        client.set_ttl_config(&TtlConfig {
+            threshold: 1_000,
+            extend_to: 5_000,
+        });
+        client.credit(&user, &100i128);
+
+        advance_ledgers(&env, 3_000);
+        client.balance(&user);
+        assert_eq!(client.balance_ttl(&user), 2_000);
+
+        advance_ledgers(&env, 1_500);
+        client.balance(&user);
+        assert_eq!(client.balance_ttl(&user), 5_000);
+    }
+
+    #[test]
+    fn anyone_can_bump_an_account() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (contract_id, client, _admin, user, _non_admin) = setup(&env);
+        client.set_ttl_config(&TtlConfig {
+            threshold: 1_000,
+            extend_to: 5_000,
+        });
+        client.credit(&user, &100i128);
+        advance_ledgers(&env, 4_500);
+        assert_eq!(client.balance_ttl(&user), 500);
+
+        env.set_auths(&[]);
+        client.bump(&user);
+
+        assert_eq!(client.balance_ttl(&user), 5_000);
+        env.as_contract(&contract_id, || {
+            let key = DataKey::Balance(user.clone());
+            assert_eq!(env.storage().persistent().get_ttl(&key), 5_000);
+        });
+    }
+
+    #[test]
+    fn bump_ignores_accounts_without_balance() {
+        let env = Env::default();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+
+        client.bump(&user);
+
+        assert_eq!(client.balance_ttl(&user), 0);
+    }
+
+    #[test]
+    fn listing_entries_extends_them() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (contract_id, client, _admin, user, _non_admin) = setup(&env);
+        client.set_ttl_config(&TtlConfig {
+            threshold: 1_000,
+            extend_to: 5_000,
+        });
+        client.credit(&user, &100i128);
+        advance_ledgers(&env, 4_000);
+
+        client.list_entries(&user, &None, &10);
+
+        env.as_contract(&contract_id, || {
+            let storage = env.storage().persistent();
+            assert_eq!(storage.get_ttl(&DataKey::JournalEntry(user.clone(), 0)), 5_000);
+            assert_eq!(storage.get_ttl(&DataKey::JournalCount(user.clone())), 5_000);
+        });
+    }
+
+    #[test]
+    fn bump_extends_other_user_entries() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (contract_id, client, _admin, user, _non_admin) = setup(&env);
+        client.set_ttl_config(&TtlConfig {
+            threshold: 1_000,
+            extend_to: 5_000,
+        });
+        let rent = Symbol::new(&env, "rent");
+        client.credit_bucket(&user, &rent, &100i128);
+        client.set_credit_limit(&user, &50i128);
+        client.hold(&user, &10i128, &hold_id(&env, 1), &DAY);
+        client.freeze(&user, &1);
+        advance_ledgers(&env, 4_000);
+
+        env.set_auths(&[]);
+        client.bump(&user);
+
+        env.as_contract(&contract_id, || {
+            let storage = env.storage().persistent();
+            assert_eq!(storage.get_ttl(&DataKey::Buckets(user.clone())), 5_000);
+            assert_eq!(storage.get_ttl(&DataKey::CreditLimit(user.clone())), 5_000);
+            assert_eq!(storage.get_ttl(&DataKey::Hold(hold_id(&env, 1))), 5_000);
+            assert_eq!(storage.get_ttl(&DataKey::UserHolds(user.clone())), 5_000);
+            assert_eq!(storage.get_ttl(&DataKey::Frozen(user.clone())), 5_000);
+        });
+    }
+
+    #[test]
+    fn set_ttl_config_rejects_invalid_values() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, _user, _non_admin) = setup(&env);
+
+        assert_eq!(
+            client.try_set_ttl_config(&TtlConfig {
+                threshold: 5_000,
+                extend_to: 1_000,
+            }),
+            Err(Ok(ContractError::InvalidTtlConfig))
+        );
+        assert_eq!(
+            client.try_set_ttl_config(&TtlConfig {
+                threshold: 0,
+                extend_to: u32::MAX,
+            }),
+            Err(Ok(ContractError::InvalidTtlConfig))
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn non_admin_cannot_set_ttl_config() {
+        let env = Env::default();
+        let (contract_id, client, _admin, _user, non_admin) = setup(&env);
+        let config = TtlConfig {
+            threshold: 1_000,
+            extend_to: 5_000,
+        };
 
         env.mock_auths(&[MockAuth {
-            address: &admin,
+            address: &non_admin,
             invoke: &MockAuthInvoke {
                 contract: &contract_id,
-                fn_name: "credit",
-                args: (user.clone(), 100i128).into_val(&env),
+                fn_name: "set_ttl_config",
+                args: (config.clone(),).into_val(&env),
                 sub_invokes: &[],
             },
         }]);
+        client.set_ttl_config(&config);
+    }
 
-        assert_eq!(client.balance(&user), 0i128);
-        client.credit(&user, &100i128);
-        assert_eq!(client.balance(&user), 100i128);
+    // ============================================================================
+    // Allowance Tests
+    // ============================================================================
+
+    #[test]
+    fn debit_from_spends_allowance() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, owner, spender) = setup(&env);
+        client.credit(&owner, &1_000i128);
+
+        client.approve(&owner, &spender, &600i128, &1_000u32);
+        assert_eq!(client.allowance(&owner, &spender), 600i128);
+
+        client.debit_from(&spender, &owner, &400i128);
+
+        let event = env.events().all().last().unwrap();
+        let event_name: Symbol = event.1.get(0).unwrap().try_into_val(&env).unwrap();
+        assert_eq!(event_name, Symbol::new(&env, "debit_from"));
+        let data: (i128, i128, i128) = event.2.try_into_val(&env).unwrap();
+        assert_eq!(data, (400i128, 600i128, 400i128));
+        assert_eq!(client.balance(&owner), 600i128);
+        assert_eq!(client.balance(&spender), 400i128);
+        assert_eq!(client.allowance(&owner, &spender), 200i128);
+    }
+
+    #[test]
+    fn debit_from_keeps_funds_in_wallet() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (contract_id, client, _admin, owner, spender) = setup(&env);
+        let (token, token_client, asset_client) = setup_token(&env, &client);
+        asset_client.mint(&owner, &1_000i128);
+        client.deposit(&owner, &token, &500i128);
+        client.approve(&owner, &spender, &300i128, &1_000u32);
+
+        client.debit_from(&spender, &owner, &300i128);
+
+        assert_eq!(client.total_balance(), 500i128);
+        assert_eq!(client.total_debited(), 0i128);
+        client.withdraw(&spender, &300i128, &spender);
+        assert_eq!(token_client.balance(&spender), 300i128);
+        assert_eq!(token_client.balance(&contract_id), 200i128);
+        assert_eq!(client.check_invariants().fully_backed, Some(true));
+    }
+
+    #[test]
+    fn debit_from_rejects_self_and_transfer_pause() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, owner, spender) = setup(&env);
+        client.credit(&owner, &1_000i128);
+        client.approve(&owner, &owner, &500i128, &1_000u32);
+        client.approve(&owner, &spender, &500i128, &1_000u32);
+
+        assert_eq!(
+            client.try_debit_from(&owner, &owner, &100i128),
+            Err(Ok(ContractError::SelfTransfer))
+        );
+        client.pause_operations(&PAUSE_TRANSFER);
+        assert_eq!(
+            client.try_debit_from(&spender, &owner, &100i128),
+            Err(Ok(ContractError::ContractPaused))
+        );
+    }
+
+    #[test]
+    fn debit_from_fails_beyond_allowance() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, owner, spender) = setup(&env);
+        client.credit(&owner, &1_000i128);
+        client.approve(&owner, &spender, &100i128, &1_000u32);
+
+        assert_eq!(
+            client.try_debit_from(&spender, &owner, &101i128),
+            Err(Ok(ContractError::InsufficientAllowance))
+        );
+        assert_eq!(client.balance(&owner), 1_000i128);
+        assert_eq!(client.allowance(&owner, &spender), 100i128);
+    }
+
+    #[test]
+    fn debit_from_still_requires_available_balance() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, owner, spender) = setup(&env);
+        client.credit(&owner, &50i128);
+        client.approve(&owner, &spender, &100i128, &1_000u32);
+
+        assert_eq!(
+            client.try_debit_from(&spender, &owner, &80i128),
+            Err(Ok(ContractError::InsufficientBalance))
+        );
+        assert_eq!(client.allowance(&owner, &spender), 100i128);
+    }
+
+    #[test]
+    fn expired_allowance_stops_working() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, owner, spender) = setup(&env);
+        client.credit(&owner, &1_000i128);
+        let expiration = env.ledger().sequence() + 10;
+        client.approve(&owner, &spender, &500i128, &expiration);
+
+        env.ledger().with_mut(|li| li.sequence_number = expiration + 1);
+
+        assert_eq!(client.allowance(&owner, &spender), 0i128);
+        assert_eq!(
+            client.try_debit_from(&spender, &owner, &100i128),
+            Err(Ok(ContractError::InsufficientAllowance))
+        );
+    }
+
+    #[test]
+    fn approve_rejects_past_expiration_and_negative_amount() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, owner, spender) = setup(&env);
+        env.ledger().with_mut(|li| li.sequence_number = 100);
+
+        assert_eq!(
+            client.try_approve(&owner, &spender, &10i128, &99u32),
+            Err(Ok(ContractError::InvalidExpiry))
+        );
+        assert_eq!(
+            client.try_approve(&owner, &spender, &-1i128, &1_000u32),
+            Err(Ok(ContractError::InvalidAmount))
+        );
     }
 
     #[test]
-    fn credit_accumulates_balance() {
+    fn approve_zero_revokes_allowance() {
         let env = Env::default();
-        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, owner, spender) = setup(&env);
+        client.approve(&owner, &spender, &500i128, &1_000u32);
 
-        env.mock_auths(&[MockAuth {
-            address: &admin,
-            invoke: &MockAuthInvoke {
-                contract: &contract_id,
-                fn_name: "credit",
-                args: (user.clone(), 50i128).into_val(&env),
-                sub_invokes: &[],
-            },
-        }]);
-        client.credit(&user, &50i128);
-        assert_eq!(client.balance(&user), 50i128);
+        client.approve(&owner, &spender, &0i128, &0u32);
 
-        env.mock_auths(&[MockAuth {
-            address: &admin,
-            invoke: &MockAuthInvoke {
-                contract: &contract_id,
-                fn_name: "credit",
-                args: (user.clone(), 75i128).into_val(&env),
-                sub_invokes: &[],
-            },
-        }]);
-        client.credit(&user, &75i128);
-        assert_eq!(client.balance(&user), 125i128);
+        assert_eq!(client.allowance(&owner, &spender), 0i128);
     }
 
     #[test]
-    #[should_panic(expected = "amount must be positive")]
-    fn credit_fails_with_zero_amount() {
+    fn debit_from_fails_when_debits_paused() {
         let env = Env::default();
-        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, owner, spender) = setup(&env);
+        client.credit(&owner, &1_000i128);
+        client.approve(&owner, &spender, &500i128, &1_000u32);
+        client.pause_operations(&PAUSE_DEBIT);
+
+        assert_eq!(
+            client.try_debit_from(&spender, &owner, &100i128),
+            Err(Ok(ContractError::ContractPaused))
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn approve_requires_owner_auth() {
+        let env = Env::default();
+        let (contract_id, client, _admin, owner, spender) = setup(&env);
 
         env.mock_auths(&[MockAuth {
-            address: &admin,
+            address: &spender,
             invoke: &MockAuthInvoke {
                 contract: &contract_id,
-                fn_name: "credit",
-                args: (user.clone(), 0i128).into_val(&env),
+                fn_name: "approve",
+                args: (owner.clone(), spender.clone(), 500i128, 1_000u32).into_val(&env),
                 sub_invokes: &[],
             },
         }]);
-
-        client.credit(&user, &0i128);
+        client.approve(&owner, &spender, &500i128, &1_000u32);
     }
 
     #[test]
-    #[should_panic(expected = "amount must be positive")]
-    fn credit_fails_with_negative_amount() {
+    #[should_panic]
+    fn debit_from_requires_spender_auth() {
         let env = Env::default();
-        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+        env.mock_all_auths();
+        let (contract_id, client, _admin, owner, spender) = setup(&env);
+        client.credit(&owner, &1_000i128);
+        client.approve(&owner, &spender, &500i128, &1_000u32);
 
         env.mock_auths(&[MockAuth {
-            address: &admin,
+            address: &owner,
             invoke: &MockAuthInvoke {
                 contract: &contract_id,
-                fn_name: "credit",
-                args: (user.clone(), -10i128).into_val(&env),
+                fn_name: "debit_from",
+                args: (spender.clone(), owner.clone(), 100i128).into_val(&env),
                 sub_invokes: &[],
             },
         }]);
-
-        client.credit(&user, &-10i128);
+        client.debit_from(&spender, &owner, &100i128);
     }
 
     // ============================================================================
-    // Debit Tests
+    // Totals & Invariant Tests
     // ============================================================================
 
     #[test]
-    fn debit_decreases_balance() {
+    fn totals_track_credits_and_debits() {
         let env = Env::default();
-        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, other) = setup(&env);
 
-        // First credit some balance
-        env.mock_auths(&[MockAuth {
-            address: &admin,
-            invoke: &MockAuthInvoke {
-                contract: &contract_id,
-                fn_name: "credit",
-                args: (user.clone(), 100i128).into_val(&env),
-                sub_invokes: &[],
-            },
-        }]);
-        client.credit(&user, &100i128);
-        assert_eq!(client.balance(&user), 100i128);
+        client.credit(&user, &500i128);
+        client.credit(&other, &300i128);
+        client.debit(&user, &200i128);
 
-        // Then debit
-        env.mock_auths(&[MockAuth {
-            address: &admin,
-            invoke: &MockAuthInvoke {
-                contract: &contract_id,
-                fn_name: "debit",
-                args: (user.clone(), 30i128).into_val(&env),
-                sub_invokes: &[],
-            },
-        }]);
-        client.debit(&user, &30i128);
-        assert_eq!(client.balance(&user), 70i128);
+        assert_eq!(client.total_balance(), 600i128);
+        assert_eq!(client.total_credited(), 800i128);
+        assert_eq!(client.total_debited(), 200i128);
     }
 
     #[test]
-    fn debit_can_reduce_balance_to_zero() {
+    fn transfers_leave_totals_unchanged() {
         let env = Env::default();
-        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, other) = setup(&env);
+        client.credit(&user, &500i128);
 
-        // Credit balance
-        env.mock_auths(&[MockAuth {
-            address: &admin,
-            invoke: &MockAuthInvoke {
-                contract: &contract_id,
-                fn_name: "credit",
-                args: (user.clone(), 50i128).into_val(&env),
-                sub_invokes: &[],
-            },
-        }]);
-        client.credit(&user, &50i128);
+        client.transfer(&user, &other, &150i128);
 
-        // Debit entire balance
-        env.mock_auths(&[MockAuth {
-            address: &admin,
-            invoke: &MockAuthInvoke {
-                contract: &contract_id,
-                fn_name: "debit",
-                args: (user.clone(), 50i128).into_val(&env),
-                sub_invokes: &[],
-            },
-        }]);
-        client.debit(&user, &50i128);
-        assert_eq!(client.balance(&user), 0i128);
+        assert_eq!(client.total_balance(), 500i128);
+        assert_eq!(client.total_credited(), 500i128);
+        assert_eq!(client.total_debited(), 0i128);
     }
 
     #[test]
-    #[should_panic(expected = "insufficient balance")]
-    fn debit_fails_with_insufficient_balance() {
+    fn migrated_legacy_balances_count_as_credited() {
         let env = Env::default();
-        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+        env.mock_all_auths();
+        let (contract_id, client, _admin, user, _non_admin) = setup(&env);
+        env.as_contract(&contract_id, || {
+            let mut legacy = Map::<Address, i128>::new(&env);
+            legacy.set(user.clone(), 250i128);
+            env.storage().instance().set(&DataKey::Balances, &legacy);
+        });
+
+        client.migrate_balances();
+
+        assert_eq!(client.total_balance(), 250i128);
+        assert_eq!(client.total_credited(), 250i128);
+        assert!(client.check_invariants().totals_consistent);
+    }
 
-        // Credit some balance
-        env.mock_auths(&[MockAuth {
-            address: &admin,
-            invoke: &MockAuthInvoke {
-                contract: &contract_id,
-                fn_name: "credit",
-                args: (user.clone(), 50i128).into_val(&env),
-                sub_invokes: &[],
-            },
-        }]);
+    #[test]
+    fn check_invariants_without_token() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        client.credit(&user, &100i128);
+
+        let report = client.check_invariants();
+
+        assert_eq!(
+            report.totals,
+            Totals {
+                balance: 100,
+                credited: 100,
+                debited: 0,
+            }
+        );
+        assert!(report.totals_consistent);
+        assert_eq!(report.owed, 100i128);
+        assert_eq!(report.token_holdings, None);
+        assert_eq!(report.fully_backed, None);
+    }
+
+    #[test]
+    fn check_invariants_compares_token_holdings() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let (token, _token_client, asset_client) = setup_token(&env, &client);
+        asset_client.mint(&user, &1_000i128);
+        client.deposit(&user, &token, &400i128);
+        client.withdraw(&user, &100i128, &user);
+
+        let report = client.check_invariants();
+        assert!(report.totals_consistent);
+        assert_eq!(report.token_holdings, Some(300i128));
+        assert_eq!(report.fully_backed, Some(true));
+
+        // An admin credit with no matching deposit leaves the wallet short.
         client.credit(&user, &50i128);
+        let report = client.check_invariants();
+        assert_eq!(report.totals.balance, 350i128);
+        assert_eq!(report.fully_backed, Some(false));
+    }
 
-        // Try to debit more than available
-        env.mock_auths(&[MockAuth {
-            address: &admin,
-            invoke: &MockAuthInvoke {
-                contract: &contract_id,
-                fn_name: "debit",
-                args: (user.clone(), 100i128).into_val(&env),
-                sub_invokes: &[],
-            },
-        }]);
+    #[test]
+    fn check_invariants_ignores_drawn_credit() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, other) = setup(&env);
+        let (token, _token_client, asset_client) = setup_token(&env, &client);
+        asset_client.mint(&user, &1_000i128);
+        client.deposit(&user, &token, &300i128);
+        client.set_credit_limit(&other, &500i128);
+        client.debit(&other, &200i128);
+        // Off-chain settled credit with no matching deposit.
+        client.credit(&user, &100i128);
 
-        client.debit(&user, &100i128);
+        let report = client.check_invariants();
+        assert_eq!(report.totals.balance, 200i128);
+        assert_eq!(report.owed, 400i128);
+        assert_eq!(report.token_holdings, Some(300i128));
+        assert_eq!(report.fully_backed, Some(false));
     }
 
+    // ============================================================================
+    // Standing Order Tests
+    // ============================================================================
+
+    const MONTH: u64 = 30 * 24 * 60 * 60;
+
     #[test]
-    #[should_panic(expected = "insufficient balance")]
-    fn debit_fails_when_balance_is_zero() {
+    fn create_standing_order_stores_schedule() {
         let env = Env::default();
-        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, landlord) = setup(&env);
+        let start = env.ledger().timestamp() + MONTH;
+
+        let order_id = client.create_standing_order(
+            &user, &landlord, &250i128, &MONTH, &start, &12u32,
+        );
+
+        assert_eq!(order_id, 0u64);
+        assert_eq!(
+            client.get_standing_order(&order_id),
+            Some(StandingOrder {
+                user: user.clone(),
+                beneficiary: landlord.clone(),
+                amount: 250,
+                interval_secs: MONTH,
+                next_due: start,
+                remaining: 12,
+                executed: 0,
+                missed: 0,
+            })
+        );
+        let second = client.create_standing_order(&user, &landlord, &10i128, &MONTH, &start, &1u32);
+        assert_eq!(second, 1u64);
+    }
 
-        env.mock_auths(&[MockAuth {
-            address: &admin,
-            invoke: &MockAuthInvoke {
-                contract: &contract_id,
-                fn_name: "debit",
-                args: (user.clone(), 1i128).into_val(&env),
-                sub_invokes: &[],
-            },
-        }]);
+    #[test]
+    fn create_standing_order_rejects_invalid_schedule() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, landlord) = setup(&env);
+
+        assert_eq!(
+            client.try_create_standing_order(&user, &landlord, &250i128, &0u64, &0u64, &12u32),
+            Err(Ok(ContractError::InvalidSchedule))
+        );
+        assert_eq!(
+            client.try_create_standing_order(&user, &landlord, &250i128, &MONTH, &0u64, &0u32),
+            Err(Ok(ContractError::InvalidSchedule))
+        );
+        assert_eq!(
+            client.try_create_standing_order(&user, &user, &250i128, &MONTH, &0u64, &1u32),
+            Err(Ok(ContractError::SelfTransfer))
+        );
+        assert_eq!(
+            client.try_create_standing_order(&user, &landlord, &0i128, &MONTH, &0u64, &1u32),
+            Err(Ok(ContractError::InvalidAmount))
+        );
+    }
 
-        client.debit(&user, &1i128);
+    #[test]
+    fn create_standing_order_rejects_frozen_user() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, landlord) = setup(&env);
+        client.freeze(&user, &1u32);
+
+        assert_eq!(
+            client.try_create_standing_order(&user, &landlord, &250i128, &MONTH, &0u64, &12u32),
+            Err(Ok(ContractError::AccountFrozen))
+        );
     }
 
     #[test]
-    #[should_panic(expected = "amount must be positive")]
-    fn debit_fails_with_zero_amount() {
+    fn execute_due_pays_beneficiary_and_advances_schedule() {
         let env = Env::default();
-        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, landlord) = setup(&env);
+        client.credit(&user, &1_000i128);
+        let start = env.ledger().timestamp() + MONTH;
+        let order_id = client.create_standing_order(
+            &user, &landlord, &250i128, &MONTH, &start, &3u32,
+        );
+
+        assert_eq!(
+            client.try_execute_due(&order_id),
+            Err(Ok(ContractError::StandingOrderNotDue))
+        );
+
+        env.ledger().with_mut(|li| li.timestamp = start);
+        env.set_auths(&[]);
+        assert!(client.execute_due(&order_id));
+
+        let event = env.events().all().last().unwrap();
+        let event_name: Symbol = event.1.get(0).unwrap().try_into_val(&env).unwrap();
+        assert_eq!(event_name, Symbol::new(&env, "order_executed"));
+        let data: (i128, i128, i128) = event.2.try_into_val(&env).unwrap();
+        assert_eq!(data, (250i128, 750i128, 250i128));
+
+        let order = client.get_standing_order(&order_id).unwrap();
+        assert_eq!(order.next_due, start + MONTH);
+        assert_eq!(order.remaining, 2);
+        assert_eq!(order.executed, 1);
+        assert_eq!(
+            client.try_execute_due(&order_id),
+            Err(Ok(ContractError::StandingOrderNotDue))
+        );
+    }
 
-        // First credit some balance
-        env.mock_auths(&[MockAuth {
-            address: &admin,
-            invoke: &MockAuthInvoke {
-                contract: &contract_id,
-                fn_name: "credit",
-                args: (user.clone(), 100i128).into_val(&env),
-                sub_invokes: &[],
-            },
-        }]);
+    #[test]
+    fn execute_due_records_missed_run_when_funds_are_short() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, landlord) = setup(&env);
         client.credit(&user, &100i128);
+        let start = env.ledger().timestamp();
+        let order_id = client.create_standing_order(
+            &user, &landlord, &250i128, &MONTH, &start, &3u32,
+        );
 
-        // Try to debit zero
-        env.mock_auths(&[MockAuth {
-            address: &admin,
-            invoke: &MockAuthInvoke {
-                contract: &contract_id,
-                fn_name: "debit",
-                args: (user.clone(), 0i128).into_val(&env),
-                sub_invokes: &[],
-            },
-        }]);
+        assert!(!client.execute_due(&order_id));
+
+        let event = env.events().all().last().unwrap();
+        let event_name: Symbol = event.1.get(0).unwrap().try_into_val(&env).unwrap();
+        assert_eq!(event_name, Symbol::new(&env, "order_missed"));
+        let data: (i128, u64) = event.2.try_into_val(&env).unwrap();
+        assert_eq!(data, (250i128, start));
 
-        client.debit(&user, &0i128);
+        assert_eq!(client.balance(&user), 100i128);
+        assert_eq!(client.balance(&landlord), 0i128);
+        let order = client.get_standing_order(&order_id).unwrap();
+        assert_eq!(order.missed, 1);
+        assert_eq!(order.remaining, 2);
+        assert_eq!(order.next_due, start + MONTH);
     }
 
     #[test]
-    #[should_panic(expected = "amount must be positive")]
-    fn debit_fails_with_negative_amount() {
+    fn standing_order_finishes_after_last_run() {
         let env = Env::default();
-        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, landlord) = setup(&env);
+        client.credit(&user, &1_000i128);
+        let start = env.ledger().timestamp();
+        let order_id = client.create_standing_order(
+            &user, &landlord, &300i128, &MONTH, &start, &2u32,
+        );
+
+        client.execute_due(&order_id);
+        env.ledger().with_mut(|li| li.timestamp = start + MONTH);
+        client.execute_due(&order_id);
+
+        assert_eq!(client.balance(&user), 400i128);
+        assert_eq!(client.balance(&landlord), 600i128);
+        assert_eq!(client.get_standing_order(&order_id), None);
+        env.ledger().with_mut(|li| li.timestamp = start + 2 * MONTH);
+        assert_eq!(
+            client.try_execute_due(&order_id),
+            Err(Ok(ContractError::StandingOrderNotFound))
+        );
+    }
 
-        // First credit some balance
-        env.mock_auths(&[MockAuth {
-            address: &admin,
-            invoke: &MockAuthInvoke {
-                contract: &contract_id,
-                fn_name: "credit",
-                args: (user.clone(), 100i128).into_val(&env),
-                sub_invokes: &[],
-            },
-        }]);
-        client.credit(&user, &100i128);
+    #[test]
+    fn execute_due_fails_when_transfers_paused() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, landlord) = setup(&env);
+        client.credit(&user, &1_000i128);
+        let start = env.ledger().timestamp();
+        let order_id = client.create_standing_order(
+            &user, &landlord, &300i128, &MONTH, &start, &2u32,
+        );
+        client.pause_operations(&PAUSE_TRANSFER);
+
+        assert_eq!(
+            client.try_execute_due(&order_id),
+            Err(Ok(ContractError::ContractPaused))
+        );
+        assert_eq!(client.get_standing_order(&order_id).unwrap().remaining, 2);
+    }
+
+    #[test]
+    fn user_can_cancel_standing_order() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, landlord) = setup(&env);
+        let order_id = client.create_standing_order(
+            &user, &landlord, &300i128, &MONTH, &0u64, &2u32,
+        );
+
+        client.cancel_standing_order(&order_id);
+
+        assert_eq!(client.get_standing_order(&order_id), None);
+        assert_eq!(
+            client.try_cancel_standing_order(&order_id),
+            Err(Ok(ContractError::StandingOrderNotFound))
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn create_standing_order_requires_user_auth() {
+        let env = Env::default();
+        let (contract_id, client, _admin, user, landlord) = setup(&env);
 
-        // Try to debit negative amount
         env.mock_auths(&[MockAuth {
-            address: &admin,
+            address: &landlord,
             invoke: &MockAuthInvoke {
                 contract: &contract_id,
-                fn_name: "debit",
-                args: (user.clone(), -10i128).into_val(&env),
+                fn_name: "create_standing_order",
+                args: (user.clone(), landlord.clone(), 250i128, MONTH, 0u64, 1u32).into_val(&env),
                 sub_invokes: &[],
             },
         }]);
-
-        client.debit(&user, &-10i128);
+        client.create_standing_order(&user, &landlord, &250i128, &MONTH, &0u64, &1u32);
     }
 
     // ============================================================================
-    // Balance Tests
+    // Credit Line Tests
     // ============================================================================
 
     #[test]
-    fn balance_returns_zero_for_new_user() {
+    fn debit_can_draw_on_credit_line() {
         let env = Env::default();
+        env.mock_all_auths();
         let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
-        let new_user = Address::generate(&env);
+        client.credit(&user, &100i128);
+        client.set_credit_limit(&user, &500i128);
 
-        assert_eq!(client.balance(&user), 0i128);
-        assert_eq!(client.balance(&new_user), 0i128);
+        client.debit(&user, &400i128);
+
+        assert_eq!(client.balance(&user), -300i128);
+        assert_eq!(client.credit_limit(&user), 500i128);
+        assert_eq!(client.credit_used(&user), 300i128);
+        assert_eq!(client.credit_available(&user), 200i128);
     }
 
     #[test]
-    fn balance_reflects_credit_and_debit_operations() {
+    fn debit_cannot_exceed_credit_limit() {
         let env = Env::default();
-        let (contract_id, client, admin, user, _non_admin) = setup(&env);
-
-        // Initial balance
-        assert_eq!(client.balance(&user), 0i128);
-
-        // After credit
-        env.mock_auths(&[MockAuth {
-            address: &admin,
-            invoke: &MockAuthInvoke {
-                contract: &contract_id,
-                fn_name: "credit",
-                args: (user.clone(), 200i128).into_val(&env),
-                sub_invokes: &[],
-            },
-        }]);
-        client.credit(&user, &200i128);
-        assert_eq!(client.balance(&user), 200i128);
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        client.set_credit_limit(&user, &500i128);
+        client.debit(&user, &500i128);
+
+        assert_eq!(
+            client.try_debit(&user, &1i128),
+            Err(Ok(ContractError::InsufficientBalance))
+        );
+        assert_eq!(client.credit_available(&user), 0i128);
+    }
 
-        // After debit
-        env.mock_auths(&[MockAuth {
-            address: &admin,
-            invoke: &MockAuthInvoke {
-                contract: &contract_id,
-                fn_name: "debit",
-                args: (user.clone(), 80i128).into_val(&env),
-                sub_invokes: &[],
-            },
-        }]);
-        client.debit(&user, &80i128);
-        assert_eq!(client.balance(&user), 120i128);
+    #[test]
+    fn unused_credit_line_reports_full_availability() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        client.credit(&user, &100i128);
+        client.set_credit_limit(&user, &500i128);
+
+        assert_eq!(client.credit_used(&user), 0i128);
+        assert_eq!(client.credit_available(&user), 500i128);
     }
 
-    // ============================================================================
-    // Admin Authorization Tests
-    // ============================================================================
+    #[test]
+    fn credits_repay_outstanding_credit_first() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        client.set_credit_limit(&user, &500i128);
+        client.debit(&user, &300i128);
+
+        client.credit(&user, &400i128);
+
+        let repaid = env
+            .events()
+            .all()
+            .iter()
+            .find(|event| {
+                let name: Symbol = event.1.get(0).unwrap().try_into_val(&env).unwrap();
+                name == Symbol::new(&env, "credit_repaid")
+            })
+            .unwrap();
+        let data: (i128, i128) = repaid.2.try_into_val(&env).unwrap();
+        assert_eq!(data, (300i128, 0i128));
+        assert_eq!(client.balance(&user), 100i128);
+        assert_eq!(client.credit_used(&user), 0i128);
+    }
 
+    #[test]
+    fn set_credit_limit_emits_event_and_rejects_negative() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        client.set_credit_limit(&user, &500i128);
+
+        client.set_credit_limit(&user, &200i128);
+
+        let event = env.events().all().last().unwrap();
+        let event_name: Symbol = event.1.get(0).unwrap().try_into_val(&env).unwrap();
+        assert_eq!(event_name, Symbol::new(&env, "credit_limit"));
+        let data: (i128, i128) = event.2.try_into_val(&env).unwrap();
+        assert_eq!(data, (500i128, 200i128));
+        assert_eq!(
+            client.try_set_credit_limit(&user, &-1i128),
+            Err(Ok(ContractError::InvalidAmount))
+        );
+    }
 
     #[test]
+    fn withdraw_cannot_draw_on_credit_line() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let (token, _token_client, asset_client) = setup_token(&env, &client);
+        asset_client.mint(&user, &1_000i128);
+        client.deposit(&user, &token, &100i128);
+        client.set_credit_limit(&user, &500i128);
+
+        assert_eq!(
+            client.try_withdraw(&user, &150i128, &user),
+            Err(Ok(ContractError::InsufficientBalance))
+        );
+        client.withdraw(&user, &100i128, &user);
+        assert_eq!(client.balance(&user), 0i128);
+    }
 
-    #[should_panic]
+    #[test]
+    fn transfers_cannot_draw_on_credit_line() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, other) = setup(&env);
+        client.credit(&user, &100i128);
+        client.set_credit_limit(&user, &500i128);
+        client.approve(&user, &other, &500i128, &1_000u32);
+        let order_id = client.create_standing_order(&user, &other, &150i128, &MONTH, &0u64, &1u32);
+
+        assert_eq!(
+            client.try_transfer(&user, &other, &150i128),
+            Err(Ok(ContractError::InsufficientBalance))
+        );
+        assert_eq!(
+            client.try_debit_from(&other, &user, &150i128),
+            Err(Ok(ContractError::InsufficientBalance))
+        );
+        assert!(!client.execute_due(&order_id));
+        assert_eq!(client.balance(&user), 100i128);
+        assert_eq!(client.balance(&other), 0i128);
 
-    fn non_admin_cannot_credit() {
+        // Admin debits and captures may still use the credit line.
+        client.hold(&user, &300i128, &hold_id(&env, 1), &1_000u64);
+        client.capture(&hold_id(&env, 1), &300i128);
+        assert_eq!(client.balance(&user), -200i128);
+    }
 
+    #[test]
+    #[should_panic]
+    fn non_admin_cannot_set_credit_limit() {
         let env = Env::default();
-
         let (contract_id, client, _admin, user, non_admin) = setup(&env);
 
-
-
         env.mock_auths(&[MockAuth {
-
             address: &non_admin,
-
             invoke: &MockAuthInvoke {
-
                 contract: &contract_id,
-
-                fn_name: "credit",
-
-                args: (user.clone(), 100i128).into_val(&env),
-
+                fn_name: "set_credit_limit",
+                args: (user.clone(), 500i128).into_val(&env),
                 sub_invokes: &[],
-
             },
-
         }]);
-
-
-
-        client.credit(&user, &100i128);
-
+        client.set_credit_limit(&user, &500i128);
     }
 
+    // ============================================================================
+    // Fee Schedule Tests
+    // ============================================================================
 
+    fn set_fee(env: &Env, client: &RentWalletClient<'_>, bps: u32, min_fee: i128) -> Address {
+        let treasury = Address::generate(env);
+        client.set_fee_config(&Some(FeeConfig {
+            bps,
+            min_fee,
+            treasury: treasury.clone(),
+        }));
+        treasury
+    }
 
     #[test]
+    fn debit_splits_fee_to_treasury() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let treasury = set_fee(&env, &client, 150, 5);
+        client.credit(&user, &10_000i128);
 
-    #[should_panic]
+        client.debit(&user, &2_000i128);
 
-    fn non_admin_cannot_debit() {
+        let event = env.events().all().last().unwrap();
+        let data: (i128, i128, i128, i128) = event.2.try_into_val(&env).unwrap();
+        assert_eq!(data, (2_000i128, 30i128, 1_970i128, 8_000i128));
+        assert_eq!(client.balance(&user), 8_000i128);
+        assert_eq!(client.balance(&treasury), 30i128);
+    }
 
+    #[test]
+    fn small_debits_pay_minimum_fee() {
         let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let treasury = set_fee(&env, &client, 150, 5);
+        client.credit(&user, &1_000i128);
 
-        let (contract_id, client, _admin, user, non_admin) = setup(&env);
+        assert_eq!(client.quote_fee(&100i128), 5i128);
+        assert_eq!(client.quote_fee(&3i128), 3i128);
+        client.debit(&user, &100i128);
 
+        assert_eq!(client.balance(&treasury), 5i128);
+    }
 
+    #[test]
+    fn fees_stay_in_wallet_totals() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        set_fee(&env, &client, 100, 0);
+        client.credit(&user, &1_000i128);
 
-        env.mock_auths(&[MockAuth {
+        client.debit(&user, &500i128);
 
-            address: &non_admin,
+        assert_eq!(client.total_debited(), 495i128);
+        assert_eq!(client.total_balance(), 505i128);
+        assert!(client.check_invariants().totals_consistent);
+    }
 
-            invoke: &MockAuthInvoke {
+    #[test]
+    fn debit_with_ref_charges_fee() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let treasury = set_fee(&env, &client, 1_000, 0);
+        client.credit(&user, &1_000i128);
 
-                contract: &contract_id,
+        client.debit_with_ref(&user, &500i128, &hold_id(&env, 7));
 
-                fn_name: "debit",
+        assert_eq!(client.balance(&user), 500i128);
+        assert_eq!(client.balance(&treasury), 50i128);
+    }
 
-                args: (user.clone(), 1i128).into_val(&env),
+    #[test]
+    fn capture_charges_fee() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let treasury = set_fee(&env, &client, 1_000, 0);
+        client.credit(&user, &1_000i128);
+        client.hold(&user, &400i128, &hold_id(&env, 1), &1_000u64);
 
-                sub_invokes: &[],
+        client.capture(&hold_id(&env, 1), &400i128);
 
-            },
+        let event = env.events().all().last().unwrap();
+        let data: (BytesN<32>, i128, i128, i128, i128) = event.2.try_into_val(&env).unwrap();
+        assert_eq!(data, (hold_id(&env, 1), 400i128, 40i128, 360i128, 600i128));
+        assert_eq!(client.balance(&treasury), 40i128);
+        assert_eq!(client.total_debited(), 360i128);
+    }
 
-        }]);
+    #[test]
+    fn frozen_treasury_still_collects_fees() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let treasury = set_fee(&env, &client, 1_000, 0);
+        client.credit(&user, &1_000i128);
+        client.freeze(&treasury, &1u32);
 
+        client.debit(&user, &500i128);
 
+        assert_eq!(client.balance(&user), 500i128);
+        assert_eq!(client.balance(&treasury), 50i128);
+    }
 
-        client.debit(&user, &1i128);
+    #[test]
+    fn removing_fee_config_stops_fees() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let treasury = set_fee(&env, &client, 1_000, 0);
+        client.credit(&user, &1_000i128);
 
-    }
+        client.set_fee_config(&None);
+        client.debit(&user, &500i128);
 
+        assert_eq!(client.fee_config(), None);
+        assert_eq!(client.balance(&treasury), 0i128);
+    }
 
     #[test]
-    fn admin_can_set_admin() {
+    fn set_fee_config_rejects_invalid_values() {
         let env = Env::default();
-        let (contract_id, client, admin, user, _non_admin) = setup(&env);
-        let new_admin = Address::generate(&env);
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, _user, _non_admin) = setup(&env);
+        let treasury = Address::generate(&env);
+
+        assert_eq!(
+            client.try_set_fee_config(&Some(FeeConfig {
+                bps: 10_001,
+                min_fee: 0,
+                treasury: treasury.clone(),
+            })),
+            Err(Ok(ContractError::InvalidFeeConfig))
+        );
+        assert_eq!(
+            client.try_set_fee_config(&Some(FeeConfig {
+                bps: 100,
+                min_fee: -1,
+                treasury,
+            })),
+            Err(Ok(ContractError::InvalidFeeConfig))
+        );
+    }
 
-        // Original admin can set new admin
-        env.mock_auths(&[MockAuth {
-            address: &admin,
-            invoke: &MockAuthInvoke {
-                contract: &contract_id,
-                fn_name: "set_admin",
-                args: (new_admin.clone(),).into_val(&env),
-                sub_invokes: &[],
-            },
-        }]);
-        client.set_admin(&new_admin);
+    #[test]
+    #[should_panic]
+    fn non_admin_cannot_set_fee_config() {
+        let env = Env::default();
+        let (contract_id, client, _admin, _user, non_admin) = setup(&env);
+        let config = Some(FeeConfig {
+            bps: 100,
+            min_fee: 0,
+            treasury: non_admin.clone(),
+        });
 
-        // New admin should be able to perform admin operations
         env.mock_auths(&[MockAuth {
-            address: &new_admin,
+            address: &non_admin,
             invoke: &MockAuthInvoke {
                 contract: &contract_id,
-                fn_name: "credit",
-                args: (user.clone(), 50i128).into_val(&env),
+                fn_name: "set_fee_config",
+                args: (config.clone(),).into_val(&env),
                 sub_invokes: &[],
             },
         }]);
-        client.credit(&user, &50i128);
-        assert_eq!(client.balance(&user), 50i128);
+        client.set_fee_config(&config);
     }
 
+    // ============================================================================
+    // Account Index Tests
+    // ============================================================================
 
     #[test]
+    fn accounts_are_indexed_once_in_first_seen_order() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, other) = setup(&env);
+        assert_eq!(client.account_count(), 0u64);
 
-    #[should_panic]
+        client.credit(&other, &100i128);
+        client.credit(&user, &50i128);
+        client.credit(&other, &25i128);
+        client.debit(&user, &50i128);
 
-    fn non_admin_cannot_set_admin() {
+        assert_eq!(client.account_count(), 2u64);
+        let page = client.list_accounts(&None, &10u32);
+        let mut expected = soroban_sdk::Vec::new(&env);
+        expected.push_back((other.clone(), 125i128));
+        expected.push_back((user.clone(), 0i128));
+        assert_eq!(
+            page,
+            AccountPage {
+                accounts: expected,
+                has_next: false,
+                next_cursor: 0,
+            }
+        );
+    }
 
+    #[test]
+    fn accounts_holding_only_other_assets_are_indexed() {
         let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let ngn = Address::generate(&env);
+        client.add_asset(&ngn);
 
-        let (contract_id, client, _admin, _user, non_admin) = setup(&env);
-
-        let new_admin = Address::generate(&env);
+        client.credit_asset(&user, &ngn, &1_000i128);
+        client.debit_asset(&user, &ngn, &100i128);
 
+        assert_eq!(client.account_count(), 1u64);
+        let page = client.list_accounts(&None, &10u32);
+        assert_eq!(page.accounts.get(0), Some((user.clone(), 0i128)));
+        assert_eq!(client.balance_asset(&user, &ngn), 900i128);
+    }
 
+    #[test]
+    fn list_accounts_paginates_without_gaps() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, _user, _non_admin) = setup(&env);
+        let mut users = std::vec::Vec::new();
+        for i in 0..5 {
+            let user = Address::generate(&env);
+            client.credit(&user, &(100i128 + i));
+            users.push(user);
+        }
 
-        env.mock_auths(&[MockAuth {
+        let first = client.list_accounts(&None, &2u32);
+        assert!(first.has_next);
+        let second = client.list_accounts(&Some(first.next_cursor), &2u32);
+        assert!(second.has_next);
+        let third = client.list_accounts(&Some(second.next_cursor), &2u32);
+        assert!(!third.has_next);
+
+        let mut seen = std::vec::Vec::new();
+        for page in [first, second, third] {
+            for (user, balance) in page.accounts.iter() {
+                seen.push((user, balance));
+            }
+        }
+        let expected: std::vec::Vec<(Address, i128)> = users
+            .into_iter()
+            .enumerate()
+            .map(|(i, user)| (user, 100 + i as i128))
+            .collect();
+        assert_eq!(seen, expected);
+    }
 
-            address: &non_admin,
+    #[test]
+    fn transfers_index_new_accounts() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, other) = setup(&env);
+        client.credit(&user, &100i128);
 
-            invoke: &MockAuthInvoke {
+        client.transfer(&user, &other, &40i128);
 
-                contract: &contract_id,
+        assert_eq!(client.account_count(), 2u64);
+        let page = client.list_accounts(&Some(0u64), &10u32);
+        assert_eq!(page.accounts.get(0).unwrap(), (other, 40i128));
+    }
 
-                fn_name: "set_admin",
+    #[test]
+    fn account_index_shares_balance_ttl() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (contract_id, client, _admin, user, _non_admin) = setup(&env);
+        client.set_ttl_config(&TtlConfig {
+            threshold: 1_000,
+            extend_to: 5_000,
+        });
+        client.credit(&user, &100i128);
+        env.ledger().with_mut(|li| li.sequence_number += 4_500);
 
-                args: (new_admin.clone(),).into_val(&env),
+        client.bump(&user);
 
-                sub_invokes: &[],
+        env.as_contract(&contract_id, || {
+            let storage = env.storage().persistent();
+            assert_eq!(storage.get_ttl(&DataKey::AccountIndex(user.clone())), 5_000);
+            assert_eq!(storage.get_ttl(&DataKey::AccountAt(0)), 5_000);
+        });
+        assert_eq!(client.list_accounts(&None, &1u32).accounts.len(), 1);
+    }
 
-            },
+    #[test]
+    fn list_accounts_rejects_invalid_limit() {
+        let env = Env::default();
+        let (_contract_id, client, _admin, _user, _non_admin) = setup(&env);
 
-        }]);
+        assert_eq!(
+            client.try_list_accounts(&None, &0u32),
+            Err(Ok(ContractError::InvalidLimit))
+        );
+        assert_eq!(
+            client.try_list_accounts(&None, &101u32),
+            Err(Ok(ContractError::InvalidLimit))
+        );
+    }
 
+    // ============================================================================
+    // Locked & Vesting Balance Tests
+    // ============================================================================
 
+    #[test]
+    fn locked_credit_is_not_spendable_until_claimed() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let now = env.ledger().timestamp();
+        client.credit(&user, &100i128);
 
-        client.set_admin(&new_admin);
+        client.credit_locked(&user, &500i128, &(now + 30 * DAY));
 
+        assert_eq!(client.balance(&user), 100i128);
+        assert_eq!(
+            client.balance_details(&user),
+            BalanceDetails {
+                spendable: 100,
+                locked: 500,
+                claimable: 0,
+            }
+        );
+        assert_eq!(
+            client.try_debit(&user, &200i128),
+            Err(Ok(ContractError::InsufficientBalance))
+        );
+        assert_eq!(client.claim_unlocked(&user), 0i128);
     }
 
-
     #[test]
-    fn admin_can_pause() {
+    fn claim_unlocked_moves_matured_lock_into_balance() {
         let env = Env::default();
-        let (contract_id, client, admin, _user, _non_admin) = setup(&env);
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let unlock_at = env.ledger().timestamp() + 30 * DAY;
+        client.credit_locked(&user, &500i128, &unlock_at);
+
+        env.ledger().with_mut(|li| li.timestamp = unlock_at);
+        assert_eq!(client.balance_details(&user).claimable, 500i128);
+        assert_eq!(client.claim_unlocked(&user), 500i128);
+
+        let event = env.events().all().last().unwrap();
+        let data: (i128, i128) = event.2.try_into_val(&env).unwrap();
+        assert_eq!(data, (500i128, 500i128));
+        assert_eq!(client.balance(&user), 500i128);
+        assert_eq!(client.locked_grants(&user).len(), 0);
+        client.debit(&user, &200i128);
+    }
 
-        env.mock_auths(&[MockAuth {
-            address: &admin,
-            invoke: &MockAuthInvoke {
-                contract: &contract_id,
-                fn_name: "pause",
-                args: ().into_val(&env),
-                sub_invokes: &[],
-            },
-        }]);
+    #[test]
+    fn vesting_grant_releases_linearly() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let start = env.ledger().timestamp() + DAY;
+        let end = start + 100 * DAY;
+        client.credit_vesting(&user, &1_000i128, &start, &end);
+
+        env.ledger().with_mut(|li| li.timestamp = start + 25 * DAY);
+        assert_eq!(client.claim_unlocked(&user), 250i128);
+        assert_eq!(
+            client.balance_details(&user),
+            BalanceDetails {
+                spendable: 250,
+                locked: 750,
+                claimable: 0,
+            }
+        );
+
+        env.ledger().with_mut(|li| li.timestamp = start + 60 * DAY);
+        assert_eq!(client.claim_unlocked(&user), 350i128);
+
+        env.ledger().with_mut(|li| li.timestamp = end + DAY);
+        assert_eq!(client.claim_unlocked(&user), 400i128);
+        assert_eq!(client.balance(&user), 1_000i128);
+        assert_eq!(client.locked_grants(&user).len(), 0);
+    }
 
-        client.pause();
-        assert!(client.is_paused());
+    #[test]
+    fn large_vesting_grant_does_not_block_claims() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let start = env.ledger().timestamp();
+        let huge = 10i128.pow(35);
+        client.credit_locked(&user, &300i128, &(start + DAY));
+        client.credit_vesting(&user, &huge, &start, &(start + 100 * DAY));
+
+        env.ledger().with_mut(|li| li.timestamp = start + 25 * DAY);
+        assert_eq!(
+            client.balance_details(&user),
+            BalanceDetails {
+                spendable: 0,
+                locked: huge - huge / 4,
+                claimable: 300 + huge / 4,
+            }
+        );
+        assert_eq!(client.claim_unlocked(&user), 300 + huge / 4);
     }
 
     #[test]
-    fn admin_can_unpause() {
+    fn locked_grants_reject_invalid_schedules() {
         let env = Env::default();
-        let (contract_id, client, admin, _user, _non_admin) = setup(&env);
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        env.ledger().with_mut(|li| li.timestamp = 10 * DAY);
+        let now = env.ledger().timestamp();
+
+        assert_eq!(
+            client.try_credit_locked(&user, &500i128, &now),
+            Err(Ok(ContractError::InvalidExpiry))
+        );
+        assert_eq!(
+            client.try_credit_vesting(&user, &500i128, &(now + DAY), &(now + DAY)),
+            Err(Ok(ContractError::InvalidSchedule))
+        );
+        assert_eq!(
+            client.try_credit_locked(&user, &0i128, &(now + DAY)),
+            Err(Ok(ContractError::InvalidAmount))
+        );
+    }
 
-        // First pause
-        env.mock_auths(&[MockAuth {
-            address: &admin,
-            invoke: &MockAuthInvoke {
-                contract: &contract_id,
-                fn_name: "pause",
-                args: ().into_val(&env),
-                sub_invokes: &[],
-            },
-        }]);
-        client.pause();
-        assert!(client.is_paused());
+    #[test]
+    #[should_panic]
+    fn claim_unlocked_requires_user_auth() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+        client.credit_locked(&user, &500i128, &(env.ledger().timestamp() + DAY));
 
-        // Then unpause
         env.mock_auths(&[MockAuth {
             address: &admin,
             invoke: &MockAuthInvoke {
                 contract: &contract_id,
-                fn_name: "unpause",
-                args: ().into_val(&env),
+                fn_name: "claim_unlocked",
+                args: (user.clone(),).into_val(&env),
                 sub_invokes: &[],
             },
         }]);
-        client.unpause();
-        assert!(!client.is_paused());
+        client.claim_unlocked(&user);
     }
 
+    // ============================================================================
+    // Bucket Tests
+    // ============================================================================
+
     #[test]
-    #[should_panic]
-    fn non_admin_cannot_pause() {
+    fn credit_bucket_earmarks_part_of_balance() {
         let env = Env::default();
-        let (contract_id, client, _admin, _user, non_admin) = setup(&env);
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let rent = Symbol::new(&env, "rent");
+        let utilities = Symbol::new(&env, "utilities");
+        client.credit(&user, &100i128);
 
-        env.mock_auths(&[MockAuth {
-            address: &non_admin,
-            invoke: &MockAuthInvoke {
-                contract: &contract_id,
-                fn_name: "pause",
-                args: ().into_val(&env),
-                sub_invokes: &[],
-            },
-        }]);
+        client.credit_bucket(&user, &rent, &500i128);
+        client.credit_bucket(&user, &utilities, &50i128);
 
-        client.pause();
+        assert_eq!(client.balance(&user), 650i128);
+        assert_eq!(client.available_balance(&user), 100i128);
+        assert_eq!(client.bucket_balance(&user, &rent), 500i128);
+        let mut expected = Map::new(&env);
+        expected.set(rent, 500i128);
+        expected.set(utilities, 50i128);
+        assert_eq!(client.buckets(&user), expected);
     }
 
     #[test]
-    #[should_panic]
-    fn non_admin_cannot_unpause() {
+    fn plain_debit_cannot_spend_bucketed_funds() {
         let env = Env::default();
-        let (contract_id, client, admin, _user, non_admin) = setup(&env);
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let rent = Symbol::new(&env, "rent");
+        client.credit(&user, &100i128);
+        client.credit_bucket(&user, &rent, &500i128);
 
-        // First pause as admin
-        env.mock_auths(&[MockAuth {
-            address: &admin,
-            invoke: &MockAuthInvoke {
-                contract: &contract_id,
-                fn_name: "pause",
-                args: ().into_val(&env),
-                sub_invokes: &[],
-            },
-        }]);
-        client.pause();
+        assert_eq!(
+            client.try_debit(&user, &150i128),
+            Err(Ok(ContractError::InsufficientBalance))
+        );
+        client.debit(&user, &100i128);
+        assert_eq!(client.balance(&user), 500i128);
+    }
 
-        // Try to unpause as non-admin
-        env.mock_auths(&[MockAuth {
-            address: &non_admin,
-            invoke: &MockAuthInvoke {
-                contract: &contract_id,
-                fn_name: "unpause",
-                args: ().into_val(&env),
-                sub_invokes: &[],
-            },
-        }]);
+    #[test]
+    fn debit_bucket_draws_only_on_that_bucket() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let rent = Symbol::new(&env, "rent");
+        let deposit = Symbol::new(&env, "deposit");
+        client.credit_bucket(&user, &rent, &500i128);
+        client.credit_bucket(&user, &deposit, &300i128);
+
+        client.debit_bucket(&user, &rent, &400i128);
+
+        let event = env.events().all().last().unwrap();
+        let data: (i128, i128, i128, i128) = event.2.try_into_val(&env).unwrap();
+        assert_eq!(data, (400i128, 0i128, 400i128, 100i128));
+        assert_eq!(client.balance(&user), 400i128);
+        assert_eq!(
+            client.try_debit_bucket(&user, &rent, &101i128),
+            Err(Ok(ContractError::InsufficientBalance))
+        );
+        assert_eq!(client.bucket_balance(&user, &deposit), 300i128);
+    }
+
+    #[test]
+    fn move_between_buckets_reallocates_balance() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let rent = Symbol::new(&env, "rent");
+        let utilities = Symbol::new(&env, "utilities");
+        client.credit(&user, &200i128);
 
-        client.unpause();
+        client.move_between_buckets(&user, &MAIN_BUCKET, &rent, &150i128);
+        client.move_between_buckets(&user, &rent, &utilities, &100i128);
+        client.move_between_buckets(&user, &utilities, &MAIN_BUCKET, &40i128);
+
+        assert_eq!(client.balance(&user), 200i128);
+        assert_eq!(client.bucket_balance(&user, &rent), 50i128);
+        assert_eq!(client.bucket_balance(&user, &utilities), 60i128);
+        assert_eq!(client.available_balance(&user), 90i128);
+        assert_eq!(
+            client.try_move_between_buckets(&user, &MAIN_BUCKET, &rent, &91i128),
+            Err(Ok(ContractError::InsufficientBalance))
+        );
+    }
+
+    #[test]
+    fn buckets_reject_reserved_and_identical_names() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let rent = Symbol::new(&env, "rent");
+
+        assert_eq!(
+            client.try_credit_bucket(&user, &MAIN_BUCKET, &100i128),
+            Err(Ok(ContractError::InvalidBucket))
+        );
+        assert_eq!(
+            client.try_move_between_buckets(&user, &rent, &rent, &10i128),
+            Err(Ok(ContractError::InvalidBucket))
+        );
     }
 
     #[test]
     #[should_panic]
-    fn credit_fails_when_paused() {
+    fn move_between_buckets_requires_user_auth() {
         let env = Env::default();
+        env.mock_all_auths();
         let (contract_id, client, admin, user, _non_admin) = setup(&env);
+        let rent = Symbol::new(&env, "rent");
+        client.credit(&user, &200i128);
 
-        // Pause the contract
         env.mock_auths(&[MockAuth {
             address: &admin,
             invoke: &MockAuthInvoke {
                 contract: &contract_id,
-                fn_name: "pause",
-                args: ().into_val(&env),
+                fn_name: "move_between_buckets",
+                args: (user.clone(), MAIN_BUCKET, rent.clone(), 100i128).into_val(&env),
                 sub_invokes: &[],
             },
         }]);
-        client.pause();
+        client.move_between_buckets(&user, &MAIN_BUCKET, &rent, &100i128);
+    }
 
-        // Try to credit while paused
-        env.mock_auths(&[MockAuth {
-            address: &admin,
-            invoke: &MockAuthInvoke {
-                contract: &contract_id,
-                fn_name: "credit",
-                args: (user.clone(), 100i128).into_val(&env),
-                sub_invokes: &[],
-            },
-        }]);
+    // ============================================================================
+    // Withdrawal Queue Tests
+    // ============================================================================
 
-        client.credit(&user, &100i128);
+    fn enable_queue(env: &Env, client: &RentWalletClient<'_>) -> Address {
+        let guardian = Address::generate(env);
+        client.set_queue_config(&Some(QueueConfig {
+            threshold: 100,
+            delay_secs: DAY,
+            guardian: guardian.clone(),
+        }));
+        guardian
     }
 
     #[test]
-    #[should_panic]
-    fn debit_fails_when_paused() {
+    fn debit_at_or_below_threshold_runs_immediately() {
         let env = Env::default();
-        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        enable_queue(&env, &client);
+        client.credit(&user, &500i128);
 
-        // First credit some balance
-        env.mock_auths(&[MockAuth {
-            address: &admin,
-            invoke: &MockAuthInvoke {
-                contract: &contract_id,
-                fn_name: "credit",
-                args: (user.clone(), 100i128).into_val(&env),
-                sub_invokes: &[],
-            },
-        }]);
-        client.credit(&user, &100i128);
+        assert_eq!(client.debit(&user, &100i128), OutflowStatus::Executed);
 
-        // Pause the contract
-        env.mock_auths(&[MockAuth {
-            address: &admin,
-            invoke: &MockAuthInvoke {
-                contract: &contract_id,
-                fn_name: "pause",
-                args: ().into_val(&env),
-                sub_invokes: &[],
-            },
-        }]);
-        client.pause();
+        assert_eq!(client.balance(&user), 400i128);
+        assert_eq!(client.get_withdrawal(&0), None);
+    }
 
-        // Try to debit while paused
-        env.mock_auths(&[MockAuth {
-            address: &admin,
-            invoke: &MockAuthInvoke {
-                contract: &contract_id,
-                fn_name: "debit",
-                args: (user.clone(), 50i128).into_val(&env),
-                sub_invokes: &[],
-            },
-        }]);
+    #[test]
+    fn large_debit_is_queued_until_delay_passes() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let guardian = enable_queue(&env, &client);
+        env.ledger().set_timestamp(1_000);
+        client.credit(&user, &500i128);
+
+        assert_eq!(client.debit(&user, &300i128), OutflowStatus::Queued(0));
+
+        assert_eq!(client.balance(&user), 500i128);
+        assert_eq!(client.available_balance(&user), 200i128);
+        assert_eq!(
+            client.get_withdrawal(&0),
+            Some(QueuedWithdrawal {
+                user: user.clone(),
+                amount: 300,
+                outflow: Outflow::Debit(None),
+                guardian,
+                execute_after: 1_000 + DAY,
+            })
+        );
+        assert_eq!(
+            client.try_execute_withdrawal(&0),
+            Err(Ok(ContractError::WithdrawalNotReady))
+        );
+
+        env.ledger().set_timestamp(1_000 + DAY);
+        client.execute_withdrawal(&0);
 
-        client.debit(&user, &50i128);
+        assert_eq!(client.balance(&user), 200i128);
+        assert_eq!(client.available_balance(&user), 200i128);
+        assert_eq!(client.get_withdrawal(&0), None);
+        assert_eq!(
+            client.try_execute_withdrawal(&0),
+            Err(Ok(ContractError::WithdrawalNotFound))
+        );
     }
 
     #[test]
-    fn balance_works_when_paused() {
+    fn queued_amount_is_reserved_from_other_debits() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        enable_queue(&env, &client);
+        client.credit(&user, &500i128);
+        client.debit(&user, &300i128);
+
+        assert_eq!(
+            client.try_debit(&user, &300i128),
+            Err(Ok(ContractError::InsufficientBalance))
+        );
+        client.debit(&user, &100i128);
+        assert_eq!(client.balance(&user), 400i128);
+    }
+
+    #[test]
+    fn guardian_can_cancel_queued_withdrawal() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        enable_queue(&env, &client);
+        client.credit(&user, &500i128);
+        client.debit(&user, &300i128);
+
+        client.cancel_withdrawal(&0);
+
+        assert_eq!(client.get_withdrawal(&0), None);
+        assert_eq!(client.balance(&user), 500i128);
+        assert_eq!(client.available_balance(&user), 500i128);
+        env.ledger().set_timestamp(2 * DAY);
+        assert_eq!(
+            client.try_execute_withdrawal(&0),
+            Err(Ok(ContractError::WithdrawalNotFound))
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn cancel_withdrawal_requires_guardian_auth() {
         let env = Env::default();
+        env.mock_all_auths();
         let (contract_id, client, admin, user, _non_admin) = setup(&env);
+        enable_queue(&env, &client);
+        client.credit(&user, &500i128);
+        client.debit(&user, &300i128);
 
-        // Credit some balance
         env.mock_auths(&[MockAuth {
             address: &admin,
             invoke: &MockAuthInvoke {
                 contract: &contract_id,
-                fn_name: "credit",
-                args: (user.clone(), 100i128).into_val(&env),
+                fn_name: "cancel_withdrawal",
+                args: (0u64,).into_val(&env),
                 sub_invokes: &[],
             },
         }]);
-        client.credit(&user, &100i128);
+        client.cancel_withdrawal(&0);
+    }
+
+    #[test]
+    fn large_withdraw_is_queued_then_pays_out_tokens() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let (token, token_client, asset_client) = setup_token(&env, &client);
+        enable_queue(&env, &client);
+        let recipient = Address::generate(&env);
+        asset_client.mint(&user, &1_000i128);
+        client.deposit(&user, &token, &500i128);
+
+        client.withdraw(&user, &400i128, &recipient);
+        assert_eq!(token_client.balance(&recipient), 0i128);
+        assert_eq!(
+            client.get_withdrawal(&0).unwrap().outflow,
+            Outflow::Withdraw(recipient.clone())
+        );
+
+        env.ledger().set_timestamp(DAY);
+        client.execute_withdrawal(&0);
+
         assert_eq!(client.balance(&user), 100i128);
+        assert_eq!(token_client.balance(&recipient), 400i128);
+        assert_eq!(token_client.balance(&contract_id), 100i128);
+    }
+
+    #[test]
+    fn queued_withdrawal_respects_pause() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        enable_queue(&env, &client);
+        client.credit(&user, &500i128);
+        client.debit(&user, &300i128);
+        env.ledger().set_timestamp(DAY);
+
+        client.pause_operations(&PAUSE_DEBIT);
+        assert_eq!(
+            client.try_execute_withdrawal(&0),
+            Err(Ok(ContractError::ContractPaused))
+        );
+        client.unpause_operations(&PAUSE_DEBIT);
+        client.execute_withdrawal(&0);
+        assert_eq!(client.balance(&user), 200i128);
+    }
+
+    #[test]
+    fn large_debit_with_ref_is_queued() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        enable_queue(&env, &client);
+        client.credit(&user, &500i128);
+
+        client.debit_with_ref(&user, &300i128, &hold_id(&env, 9));
+
+        assert_eq!(client.balance(&user), 500i128);
+        assert!(client.is_processed(&hold_id(&env, 9)));
+        assert_eq!(
+            client.get_withdrawal(&0).unwrap().outflow,
+            Outflow::Debit(Some(hold_id(&env, 9)))
+        );
+        env.ledger().set_timestamp(DAY);
+        client.execute_withdrawal(&0);
+        assert_eq!(client.balance(&user), 200i128);
+        let entry = client.list_entries(&user, &None, &10u32).entries.last().unwrap();
+        assert_eq!(entry.reference, Some(hold_id(&env, 9)));
+    }
+
+    #[test]
+    fn large_capture_is_queued() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        enable_queue(&env, &client);
+        client.credit(&user, &500i128);
+        client.hold(&user, &400i128, &hold_id(&env, 1), &(2 * DAY));
+
+        client.capture(&hold_id(&env, 1), &300i128);
+
+        assert_eq!(client.get_hold(&hold_id(&env, 1)), None);
+        assert_eq!(client.balance(&user), 500i128);
+        assert_eq!(client.available_balance(&user), 200i128);
+        env.ledger().set_timestamp(DAY);
+        client.execute_withdrawal(&0);
+        assert_eq!(client.balance(&user), 200i128);
+        assert_eq!(client.available_balance(&user), 200i128);
+    }
+
+    #[test]
+    fn cancelled_capture_leaves_funds_available() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        enable_queue(&env, &client);
+        client.credit(&user, &500i128);
+        client.hold(&user, &400i128, &hold_id(&env, 1), &(2 * DAY));
+        assert_eq!(client.capture(&hold_id(&env, 1), &300i128), OutflowStatus::Queued(0));
+
+        client.cancel_withdrawal(&0);
+
+        assert_eq!(client.get_withdrawal(&0), None);
+        assert_eq!(client.get_hold(&hold_id(&env, 1)), None);
+        assert_eq!(client.balance(&user), 500i128);
+        assert_eq!(client.available_balance(&user), 500i128);
+    }
+
+    #[test]
+    fn queued_withdraw_returns_its_id() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let (token, _token_client, asset_client) = setup_token(&env, &client);
+        enable_queue(&env, &client);
+        asset_client.mint(&user, &1_000i128);
+        client.deposit(&user, &token, &1_000i128);
+        client.debit(&user, &200i128);
+
+        let recipient = Address::generate(&env);
+        assert_eq!(client.withdraw(&user, &50i128, &recipient), OutflowStatus::Executed);
+        assert_eq!(client.withdraw(&user, &300i128, &recipient), OutflowStatus::Queued(1));
+        assert_eq!(
+            client.get_withdrawal(&1).unwrap().outflow,
+            Outflow::Withdraw(recipient)
+        );
+    }
+
+    #[test]
+    fn cancelled_bucket_debit_returns_to_bucket() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        enable_queue(&env, &client);
+        let rent = Symbol::new(&env, "rent");
+        client.credit_bucket(&user, &rent, &500i128);
+
+        client.debit_bucket(&user, &rent, &300i128);
+        assert_eq!(client.bucket_balance(&user, &rent), 200i128);
+        assert_eq!(client.available_balance(&user), 0i128);
+
+        client.cancel_withdrawal(&0);
+        assert_eq!(client.bucket_balance(&user, &rent), 500i128);
+        assert_eq!(client.balance(&user), 500i128);
+        assert_eq!(client.available_balance(&user), 0i128);
+    }
+
+    #[test]
+    fn large_asset_debit_is_queued_and_reserved() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        enable_queue(&env, &client);
+        let ngn = Address::generate(&env);
+        client.add_asset(&ngn);
+        client.credit_asset(&user, &ngn, &500i128);
+
+        client.debit_asset(&user, &ngn, &300i128);
+
+        assert_eq!(client.balance_asset(&user, &ngn), 500i128);
+        assert_eq!(
+            client.try_debit_asset(&user, &ngn, &300i128),
+            Err(Ok(ContractError::InsufficientBalance))
+        );
+        env.ledger().set_timestamp(DAY);
+        client.execute_withdrawal(&0);
+        assert_eq!(client.balance_asset(&user, &ngn), 200i128);
+    }
+
+    #[test]
+    fn guardian_keeps_veto_after_queue_config_changes() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let guardian = enable_queue(&env, &client);
+        client.credit(&user, &500i128);
+        client.debit(&user, &300i128);
+
+        client.set_queue_config(&None);
+        assert_eq!(client.get_withdrawal(&0).unwrap().guardian, guardian);
+        client.cancel_withdrawal(&0);
+
+        assert_eq!(client.get_withdrawal(&0), None);
+        assert_eq!(client.balance(&user), 500i128);
+        assert_eq!(
+            client.try_cancel_withdrawal(&0),
+            Err(Ok(ContractError::WithdrawalNotFound))
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn replaced_guardian_cannot_cancel_existing_items() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (contract_id, client, _admin, user, _non_admin) = setup(&env);
+        enable_queue(&env, &client);
+        client.credit(&user, &500i128);
+        client.debit(&user, &300i128);
+        let new_guardian = enable_queue(&env, &client);
 
-        // Pause the contract
         env.mock_auths(&[MockAuth {
-            address: &admin,
+            address: &new_guardian,
             invoke: &MockAuthInvoke {
                 contract: &contract_id,
-                fn_name: "pause",
-                args: ().into_val(&env),
+                fn_name: "cancel_withdrawal",
+                args: (0u64,).into_val(&env),
                 sub_invokes: &[],
             },
         }]);
-        client.pause();
-
-        // Balance should still be readable
-        assert_eq!(client.balance(&user), 100i128);
+        client.cancel_withdrawal(&0);
     }
 
     #[test]
-    fn is_paused_returns_false_initially() {
+    fn set_queue_config_rejects_invalid_values() {
         let env = Env::default();
+        env.mock_all_auths();
         let (_contract_id, client, _admin, _user, _non_admin) = setup(&env);
-        assert!(!client.is_paused());
+        let guardian = Address::generate(&env);
+
+        assert_eq!(
+            client.try_set_queue_config(&Some(QueueConfig {
+                threshold: 0,
+                delay_secs: DAY,
+                guardian: guardian.clone(),
+            })),
+            Err(Ok(ContractError::InvalidQueueConfig))
+        );
+        assert_eq!(
+            client.try_set_queue_config(&Some(QueueConfig {
+                threshold: 100,
+                delay_secs: 0,
+                guardian,
+            })),
+            Err(Ok(ContractError::InvalidQueueConfig))
+        );
+        assert_eq!(client.queue_config(), None);
     }
 
     // ============================================================================
-    // Event Tests
+    // Circuit Breaker Tests
     // ============================================================================
 
+    fn enable_breaker(client: &RentWalletClient<'_>) {
+        client.set_breaker_config(&Some(BreakerConfig {
+            threshold: 500,
+            window_secs: DAY,
+        }));
+    }
+
     #[test]
-    fn credit_emits_event() {
+    fn breaker_pauses_outflows_when_window_exceeds_threshold() {
         let env = Env::default();
-        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        enable_breaker(&client);
+        client.credit(&user, &1_000i128);
+
+        client.debit(&user, &300i128);
+        assert_eq!(client.window_outflow(), 300i128);
+        assert_eq!(client.pause_flags(), 0);
+
+        assert_eq!(client.debit(&user, &300i128), OutflowStatus::Blocked);
+        let tripped = env.events().all().iter().any(|event| {
+            let name: Symbol = event.1.get(0).unwrap().try_into_val(&env).unwrap();
+            name == Symbol::new(&env, "circuit_breaker")
+                && event.2.try_into_val(&env) == Ok((600i128, 500i128, DAY))
+        });
+        assert!(tripped);
+
+        // The debit that would cross the threshold did not move any funds.
+        assert_eq!(client.balance(&user), 700i128);
+        assert_eq!(client.pause_flags(), PAUSE_DEBIT | PAUSE_WITHDRAW);
+        assert_eq!(client.window_outflow(), 0i128);
+        assert_eq!(
+            client.try_debit(&user, &10i128),
+            Err(Ok(ContractError::ContractPaused))
+        );
+        client.credit(&user, &10i128);
+
+        client.unpause_operations(&(PAUSE_DEBIT | PAUSE_WITHDRAW));
+        client.debit(&user, &10i128);
+        assert_eq!(client.window_outflow(), 10i128);
+    }
 
-        env.mock_auths(&[MockAuth {
-            address: &admin,
-            invoke: &MockAuthInvoke {
-                contract: &contract_id,
-                fn_name: "credit",
-                args: (user.clone(), 100i128).into_val(&env),
-                sub_invokes: &[],
-            },
-        }]);
+    #[test]
+    fn breaker_blocks_single_outflow_above_threshold() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (contract_id, client, _admin, user, _non_admin) = setup(&env);
+        let (token, token_client, asset_client) = setup_token(&env, &client);
+        enable_breaker(&client);
+        asset_client.mint(&user, &1_000i128);
+        client.deposit(&user, &token, &1_000i128);
+
+        assert_eq!(client.withdraw(&user, &600i128, &user), OutflowStatus::Blocked);
+
+        assert_eq!(client.balance(&user), 1_000i128);
+        assert_eq!(token_client.balance(&contract_id), 1_000i128);
+        assert_eq!(client.pause_flags(), PAUSE_DEBIT | PAUSE_WITHDRAW);
+        assert_eq!(client.window_outflow(), 0i128);
+    }
 
-        client.credit(&user, &100i128);
+    #[test]
+    fn tripped_debit_with_ref_can_be_retried() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        enable_breaker(&client);
+        client.credit(&user, &1_000i128);
+        let reference = BytesN::from_array(&env, &[7u8; 32]);
+
+        assert_eq!(
+            client.try_debit_with_ref(&user, &600i128, &reference),
+            Ok(Ok(OutflowStatus::Blocked))
+        );
+        assert!(!client.is_processed(&reference));
+        assert_eq!(client.balance(&user), 1_000i128);
+
+        client.set_breaker_config(&None);
+        client.unpause_operations(&PAUSE_ALL);
+        assert_eq!(
+            client.debit_with_ref(&user, &600i128, &reference),
+            OutflowStatus::Executed
+        );
+        assert!(client.is_processed(&reference));
+        assert_eq!(client.balance(&user), 400i128);
+    }
 
-        let events = env.events().all();
-        let event = events.last().unwrap();
+    #[test]
+    fn tripped_queued_item_stays_queued() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        enable_queue(&env, &client);
+        enable_breaker(&client);
+        client.credit(&user, &1_000i128);
+        assert_eq!(client.debit(&user, &600i128), OutflowStatus::Queued(0));
+        env.ledger().set_timestamp(DAY);
 
-        let topics: soroban_sdk::Vec<soroban_sdk::Val> = event.1.clone();
-        assert_eq!(topics.len(), 2);
-        
-        let event_name: Symbol = topics.get(0).unwrap().try_into_val(&env).unwrap();
-        assert_eq!(event_name, Symbol::new(&env, "credit"));
-        
-        let event_user: Address = topics.get(1).unwrap().try_into_val(&env).unwrap();
-        assert_eq!(event_user, user);
+        assert_eq!(client.execute_withdrawal(&0), OutflowStatus::Blocked);
 
-        let data: i128 = event.2.try_into_val(&env).unwrap();
-        assert_eq!(data, 100i128);
+        assert!(client.get_withdrawal(&0).is_some());
+        assert_eq!(client.balance(&user), 1_000i128);
+        assert_eq!(client.pause_flags(), PAUSE_DEBIT | PAUSE_WITHDRAW);
     }
 
     #[test]
-    fn debit_emits_event() {
+    fn tripped_capture_keeps_hold() {
         let env = Env::default();
-        let (contract_id, client, admin, user, _non_admin) = setup(&env);
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        enable_breaker(&client);
+        client.credit(&user, &1_000i128);
+        client.hold(&user, &800i128, &hold_id(&env, 1), &DAY);
 
-        env.mock_auths(&[MockAuth {
-            address: &admin,
-            invoke: &MockAuthInvoke {
-                contract: &contract_id,
-                fn_name: "credit",
-                args: (user.clone(), 200i128).into_val(&env),
-                sub_invokes: &[],
-            },
-        }]);
-        client.credit(&user, &200i128);
+        assert_eq!(client.capture(&hold_id(&env, 1), &800i128), OutflowStatus::Blocked);
 
-        env.mock_auths(&[MockAuth {
-            address: &admin,
-            invoke: &MockAuthInvoke {
-                contract: &contract_id,
-                fn_name: "debit",
-                args: (user.clone(), 50i128).into_val(&env),
-                sub_invokes: &[],
-            },
-        }]);
-        client.debit(&user, &50i128);
+        assert_eq!(client.balance(&user), 1_000i128);
+        assert_eq!(client.get_hold(&hold_id(&env, 1)).unwrap().amount, 800i128);
+        assert_eq!(client.pause_flags(), PAUSE_DEBIT | PAUSE_WITHDRAW);
+    }
 
-        let events = env.events().all();
-        let event = events.last().unwrap();
+    #[test]
+    fn breaker_counts_debits_net_of_fees() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        enable_breaker(&client);
+        set_fee(&env, &client, 1_000, 0);
+        client.credit(&user, &1_000i128);
 
-        let topics: soroban_sdk::Vec<soroban_sdk::Val> = event.1.clone();
-        assert_eq!(topics.len(), 2);
-        
-        let event_name: Symbol = topics.get(0).unwrap().try_into_val(&env).unwrap();
-        assert_eq!(event_name, Symbol::new(&env, "debit"));
-        
-        let event_user: Address = topics.get(1).unwrap().try_into_val(&env).unwrap();
-        assert_eq!(event_user, user);
+        client.debit(&user, &550i128);
 
-        let data: i128 = event.2.try_into_val(&env).unwrap();
-        assert_eq!(data, 50i128);
+        assert_eq!(client.window_outflow(), 495i128);
+        assert_eq!(client.pause_flags(), 0);
+    }
+
+    #[test]
+    fn breaker_window_rolls_forward() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, _non_admin) = setup(&env);
+        enable_breaker(&client);
+        client.credit(&user, &1_000i128);
+
+        client.debit(&user, &400i128);
+        env.ledger().set_timestamp(DAY / 2);
+        client.debit(&user, &100i128);
+        assert_eq!(client.window_outflow(), 500i128);
+
+        env.ledger().set_timestamp(DAY);
+        assert_eq!(client.window_outflow(), 100i128);
+        client.debit(&user, &400i128);
+
+        assert_eq!(client.window_outflow(), 500i128);
+        assert_eq!(client.pause_flags(), 0);
+    }
+
+    #[test]
+    fn breaker_ignores_transfers_and_credits() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, user, non_admin) = setup(&env);
+        enable_breaker(&client);
+        client.credit(&user, &1_000i128);
+
+        client.transfer(&user, &non_admin, &800i128);
+
+        assert_eq!(client.window_outflow(), 0i128);
+        assert_eq!(client.pause_flags(), 0);
+    }
+
+    #[test]
+    fn set_breaker_config_rejects_invalid_values() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let (_contract_id, client, _admin, _user, _non_admin) = setup(&env);
+
+        assert_eq!(
+            client.try_set_breaker_config(&Some(BreakerConfig {
+                threshold: 0,
+                window_secs: DAY,
+            })),
+            Err(Ok(ContractError::InvalidBreakerConfig))
+        );
+        assert_eq!(
+            client.try_set_breaker_config(&Some(BreakerConfig {
+                threshold: 500,
+                window_secs: BREAKER_SLOTS - 1,
+            })),
+            Err(Ok(ContractError::InvalidBreakerConfig))
+        );
+        assert_eq!(client.breaker_config(), None);
+
+        enable_breaker(&client);
+        assert_eq!(
+            client.breaker_config(),
+            Some(BreakerConfig { threshold: 500, window_secs: DAY })
+        );
+        client.set_breaker_config(&None);
+        assert_eq!(client.breaker_config(), None);
     }
 }
 